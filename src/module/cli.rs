@@ -1,10 +1,17 @@
+use super::extract::{extract_module, file_name};
+use super::import::{import_spells, import_table};
 use super::inputs::*;
+use super::roundtrip::{compare_spells, compare_tables, lossy_spell_fields};
+use super::validate::{range_lines, validate_table, TableProblem};
 use crate::CommandExec;
 use async_trait::async_trait;
+use anyhow::Context;
 use clap::{AppSettings, Clap};
+use serde::de::DeserializeOwned;
 use std::fs::File;
 use std::io::Write;
-use tracing::info;
+use std::path::{Path, PathBuf};
+use tracing::{error, info, warn};
 
 #[derive(Clap, Debug)]
 #[clap(setting = AppSettings::SubcommandRequiredElseHelp)]
@@ -13,6 +20,10 @@ pub enum ModuleSubCommand {
     #[clap(name = "build")]
     BuildModule(BuildModuleArgs),
 
+    /// Take a directory of images and generate a token only module file
+    #[clap(name = "build-tokens")]
+    BuildTokens(BuildTokensArgs),
+
     /// Create a new module
     #[clap(name = "init-module")]
     CreateModule(CreateModuleArgs),
@@ -24,6 +35,22 @@ pub enum ModuleSubCommand {
     /// Create a new table file, fully populated
     #[clap(name = "create-table")]
     CreateTable(CreateTableArgs),
+
+    /// Check the ranges of every table in a module definition
+    #[clap(name = "validate-tables")]
+    ValidateTables(ValidateTablesArgs),
+
+    /// Turn the spells and tables of a module file back into YAML
+    #[clap(name = "extract")]
+    ExtractModule(ExtractModuleArgs),
+
+    /// Build a module and extract it again, reporting anything that changed
+    #[clap(name = "verify-roundtrip")]
+    VerifyRoundtrip(VerifyRoundtripArgs),
+
+    /// Convert spells from 5etools or Open5e JSON, or a table from CSV or Markdown
+    #[clap(name = "import")]
+    Import(ImportArgs),
 }
 
 #[async_trait]
@@ -31,9 +58,14 @@ impl CommandExec for ModuleSubCommand {
     async fn exec(&self) -> Result<(), anyhow::Error> {
         match self {
             ModuleSubCommand::BuildModule(args) => args.exec().await,
+            ModuleSubCommand::BuildTokens(args) => args.exec().await,
             ModuleSubCommand::CreateSpell(args) => args.exec().await,
             ModuleSubCommand::CreateTable(args) => args.exec().await,
             ModuleSubCommand::CreateModule(args) => args.exec().await,
+            ModuleSubCommand::ValidateTables(args) => args.exec().await,
+            ModuleSubCommand::ExtractModule(args) => args.exec().await,
+            ModuleSubCommand::VerifyRoundtrip(args) => args.exec().await,
+            ModuleSubCommand::Import(args) => args.exec().await,
         }
     }
 }
@@ -53,22 +85,32 @@ pub struct CreateTableArgs {
 impl CommandExec for CreateTableArgs {
     async fn exec(&self) -> Result<(), anyhow::Error> {
         let table_def = TableDefinition {
-            id: 1,
+            id: RecordId::default(),
             name: self.name.clone(),
             ranges: vec![
                 TableRange {
                     from: 1,
                     until: 10,
                     description: "low".to_owned(),
+                    results: Vec::new(),
+                    link: None,
+                    line: 0,
                 },
                 TableRange {
                     from: 11,
                     until: 100,
                     description: "high".to_owned(),
+                    results: Vec::new(),
+                    link: None,
+                    line: 0,
                 },
             ],
             description: "A simple table".to_owned(),
             formatted_text: None,
+            columns: Vec::new(),
+            dice: Some("d100".to_owned()),
+            modifier: 0,
+            path: PathBuf::new(),
         };
 
         let text = serde_yaml::to_string(&table_def)?;
@@ -99,12 +141,11 @@ pub struct CreateModuleArgs {
 impl CommandExec for CreateModuleArgs {
     async fn exec(&self) -> Result<(), anyhow::Error> {
         let table_def = ModuleDefinition {
-             name: self.name.clone(),
-             spell_files: Vec::new(),
-             table_files: Vec::new(),
-             source: self.name.clone(),
-             category: ModuleCategory::SourceBook,
-             author: "Your name here".to_owned(),
+            name: self.name.clone(),
+            source: self.name.clone(),
+            category: ModuleCategory::SourceBook,
+            author: "Your name here".to_owned(),
+            ..Default::default()
         };
 
         let text = serde_yaml::to_string(&table_def)?;
@@ -135,10 +176,11 @@ pub struct CreateSpellArgs {
 impl CommandExec for CreateSpellArgs {
     async fn exec(&self) -> Result<(), anyhow::Error> {
         let spell_def: SpellDefinition = SpellDefinition {
-            id: SpellId::default(),
+            id: RecordId::default(),
             name: self.name.clone(),
             short_description: Option::from("Something simple".to_owned()),
             duration: Option::from("1 minuite".to_owned()),
+            components: Option::from("V, S, M (a pinch of dust)".to_owned()),
             description: "
 # Simple Description
 
@@ -192,6 +234,7 @@ impl CommandExec for CreateSpellArgs {
                                 dice_type: "d4".to_owned(),
                                 count: 1,
                             }],
+                            bonus: None,
                         }],
                     }
                 ],
@@ -239,6 +282,297 @@ impl CommandExec for CreateSpellArgs {
     }
 }
 
+#[derive(Clap, Debug)]
+pub struct ValidateTablesArgs {
+    /// Path to the root module deinition
+    #[clap(long = "module-definition", short = 'm')]
+    pub module_definition: String,
+}
+
+#[async_trait]
+impl CommandExec for ValidateTablesArgs {
+    async fn exec(&self) -> Result<(), anyhow::Error> {
+        let mut root_dir = PathBuf::from(&self.module_definition);
+        root_dir.pop();
+
+        let module_def: ModuleDefinition =
+            serde_yaml::from_str(&std::fs::read_to_string(&self.module_definition)?)?;
+
+        let mut problems: Vec<TableProblem> = Vec::new();
+        for table_file in &module_def.table_files {
+            let table = load_table(&root_dir.join(table_file))?;
+            problems.extend(validate_table(&table));
+        }
+        report_table_problems(&problems)?;
+
+        info!("All {} tables are valid", module_def.table_files.len());
+        Ok(())
+    }
+}
+
+#[derive(Clap, Debug)]
+pub struct ExtractModuleArgs {
+    /// Path to the module file
+    #[clap(long = "input", short = 'i')]
+    pub input: String,
+
+    /// Directory to write the module definition into
+    #[clap(long = "output", short = 'o')]
+    pub output_dir: String,
+}
+
+#[async_trait]
+impl CommandExec for ExtractModuleArgs {
+    async fn exec(&self) -> Result<(), anyhow::Error> {
+        let extracted = extract_module(Path::new(&self.input))?;
+        let mut module_def = extracted.module;
+        let output_dir = PathBuf::from(&self.output_dir);
+
+        let mut used = Vec::new();
+        for spell in &extracted.spells {
+            let path = format!("spells/{}", file_name(&spell.name, &mut used));
+            write_yaml(&output_dir.join(&path), spell)?;
+            module_def.spell_files.push(path);
+        }
+        let mut used = Vec::new();
+        for table in &extracted.tables {
+            let path = format!("tables/{}", file_name(&table.name, &mut used));
+            write_yaml(&output_dir.join(&path), table)?;
+            module_def.table_files.push(path);
+        }
+
+        let module_file = output_dir.join("module.yaml");
+        write_yaml(&module_file, &module_def)?;
+        info!(
+            "Extracted {} spells and {} tables to {}",
+            module_def.spell_files.len(),
+            module_def.table_files.len(),
+            module_file.display()
+        );
+
+        Ok(())
+    }
+}
+
+#[derive(Clap, Debug)]
+pub struct VerifyRoundtripArgs {
+    /// Path to the root module deinition
+    #[clap(long = "module-definition", short = 'm')]
+    pub module_definition: String,
+}
+
+#[async_trait]
+impl CommandExec for VerifyRoundtripArgs {
+    async fn exec(&self) -> Result<(), anyhow::Error> {
+        let fgu_module = load_module(&self.module_definition)?;
+        let module_file =
+            std::env::temp_dir().join(format!("fguctl-roundtrip-{}.mod", std::process::id()));
+        fgu_module.process(module_file.to_str().unwrap())?;
+        let extracted = extract_module(&module_file);
+        std::fs::remove_file(&module_file)?;
+        let extracted = extracted?;
+
+        let mut problems = compare_spells(&fgu_module.spells, &extracted.spells)?;
+        problems.extend(compare_tables(&fgu_module.tables, &extracted.tables)?);
+        if !problems.is_empty() {
+            for problem in &problems {
+                error!("{}", problem);
+            }
+            anyhow::bail!("Found {} fields that don't survive a round trip", problems.len());
+        }
+
+        info!(
+            "All {} spells and {} tables survive a round trip",
+            fgu_module.spells.len(),
+            fgu_module.tables.len()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Clap, Debug)]
+pub struct ImportArgs {
+    /// Format of the input: 5etools or open5e for spells, csv or markdown for a table
+    #[clap(long = "from")]
+    pub from: String,
+
+    /// File to import
+    pub input: String,
+
+    /// Directory to write the spell or table files into
+    #[clap(long = "output", short = 'o')]
+    pub output_dir: String,
+
+    /// Module definition to add the new files to
+    #[clap(long = "module-definition", short = 'm')]
+    pub module_definition: Option<String>,
+
+    /// Name of an imported table, instead of the heading above it or the file name
+    #[clap(long = "name")]
+    pub name: Option<String>,
+}
+
+#[async_trait]
+impl CommandExec for ImportArgs {
+    async fn exec(&self) -> Result<(), anyhow::Error> {
+        let contents = std::fs::read_to_string(&self.input)?;
+        let output_dir = PathBuf::from(&self.output_dir);
+
+        match self.from.as_str() {
+            "5etools" | "open5e" => {
+                let spells = import_spells(&self.from, &contents)?;
+                let mut used = Vec::new();
+                let mut files = Vec::new();
+                for spell in &spells {
+                    let path = output_dir.join(file_name(&spell.name, &mut used));
+                    info!("Writing {}", path.display());
+                    write_yaml(&path, spell)?;
+                    files.push(path);
+                }
+                self.add_files(&files, "spells", |module_def| &mut module_def.spell_files)
+            }
+            "csv" | "markdown" => {
+                let file_stem = Path::new(&self.input)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let mut table =
+                    import_table(&self.from, &contents, self.name.as_deref(), &file_stem)?;
+                let path = output_dir.join(file_name(&table.name, &mut Vec::new()));
+                table.path = path.clone();
+                for problem in validate_table(&table) {
+                    warn!("{}", problem);
+                }
+                info!("Writing {}", path.display());
+                write_yaml(&path, &table)?;
+                self.add_files(&[path], "tables", |module_def| &mut module_def.table_files)
+            }
+            other => anyhow::bail!(
+                "Unable to import from {}, use 5etools, open5e, csv or markdown",
+                other
+            ),
+        }
+    }
+}
+
+impl ImportArgs {
+    fn add_files<F>(&self, files: &[PathBuf], kind: &str, list: F) -> Result<(), anyhow::Error>
+    where
+        F: Fn(&mut ModuleDefinition) -> &mut Vec<String>,
+    {
+        match &self.module_definition {
+            Some(module_definition) => add_to_module(module_definition, files, list),
+            None => {
+                info!(
+                    "Imported {} {}. You will need to add them to your module definition.",
+                    files.len(),
+                    kind
+                );
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Adds files to one of the lists of a module definition, relative to the module.
+fn add_to_module<F>(
+    module_definition: &str,
+    files: &[PathBuf],
+    list: F,
+) -> Result<(), anyhow::Error>
+where
+    F: Fn(&mut ModuleDefinition) -> &mut Vec<String>,
+{
+    let mut module_def: ModuleDefinition =
+        serde_yaml::from_str(&std::fs::read_to_string(module_definition)?)?;
+    let mut root_dir = PathBuf::from(module_definition);
+    root_dir.pop();
+    let root_dir = std::fs::canonicalize(root_dir.join("."))?;
+
+    let mut added = 0;
+    for file in files {
+        let relative_path: Vec<String> = std::fs::canonicalize(file)?
+            .strip_prefix(&root_dir)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "{} is outside of the module directory {}",
+                    file.display(),
+                    root_dir.display()
+                )
+            })?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let relative_path = relative_path.join("/");
+
+        let list = list(&mut module_def);
+        if !list.contains(&relative_path) {
+            list.push(relative_path);
+            added += 1;
+        }
+    }
+
+    write_yaml(Path::new(module_definition), &module_def)?;
+    info!("Added {} files to {}", added, module_definition);
+    Ok(())
+}
+
+fn write_yaml<T>(path: &Path, value: &T) -> Result<(), anyhow::Error>
+where
+    T: serde::Serialize,
+{
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut f = File::create(path)?;
+    f.write_all(serde_yaml::to_string(value)?.as_bytes())?;
+    Ok(())
+}
+
+/// Reads a table file, remembering the path and the line each range starts on so
+/// validation problems can point back at the YAML.
+/// Reads every record file of one type, relative to the module definition.
+fn load_records<T: DeserializeOwned>(
+    root: &Path,
+    files: &[String],
+) -> Result<Vec<T>, anyhow::Error> {
+    files
+        .iter()
+        .map(|file| {
+            let path = root.join(file);
+            info!("Processing {}", path.display());
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Unable to read {}", path.display()))?;
+            serde_yaml::from_str(&contents)
+                .with_context(|| format!("Unable to parse {}", path.display()))
+        })
+        .collect()
+}
+
+fn load_table(path: &Path) -> Result<TableDefinition, anyhow::Error> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut table: TableDefinition = serde_yaml::from_str(&contents)?;
+    table.path = path.to_path_buf();
+    for (range, line) in table.ranges.iter_mut().zip(range_lines(&contents)) {
+        range.line = line;
+    }
+    Ok(table)
+}
+
+fn report_table_problems(problems: &[TableProblem]) -> Result<(), anyhow::Error> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    for problem in problems {
+        error!("{}", problem);
+    }
+    Err(anyhow::anyhow!(
+        "Found {} problems with table ranges",
+        problems.len()
+    ))
+}
+
 #[derive(Clap, Debug)]
 pub struct BuildModuleArgs {
     /// Path to the root module deinition
@@ -253,45 +587,282 @@ pub struct BuildModuleArgs {
 #[async_trait]
 impl CommandExec for BuildModuleArgs {
     async fn exec(&self) -> Result<(), anyhow::Error> {
-        use std::path::PathBuf;
+        load_module(&self.module_definition)?.process(&self.output)
+    }
+}
 
-        let mut spells: Vec<SpellDefinition> = Vec::new();
-        let mut tables: Vec<TableDefinition> = Vec::new();
+/// Reads a module definition and every file it lists.
+fn load_module(module_definition: &str) -> Result<super::output::FGUModule, anyhow::Error> {
+    let mut tables: Vec<TableDefinition> = Vec::new();
+    let mut stories: Vec<StoryDefinition> = Vec::new();
+
+    let mut root_dir = PathBuf::from(module_definition);
+    root_dir.pop();
+
+    let mut module_def: ModuleDefinition =
+        serde_yaml::from_str(&std::fs::read_to_string(module_definition)?)?;
+
+    info!("Processing {} spells...", module_def.spell_files.len());
+    let spells: Vec<SpellDefinition> = load_records(&root_dir, &module_def.spell_files)?;
+    for spell in &spells {
+        for field in lossy_spell_fields(spell) {
+            warn!(
+                "Spell {}: `{}` can't be stored in a module and will be lost",
+                spell.name, field
+            );
+        }
+    }
 
-        let mut root_dir = PathBuf::from(&self.module_definition);
-        root_dir.pop();
+    info!("Processing {} tables...", module_def.table_files.len());
+    for table_file in &module_def.table_files {
+        let table_file = root_dir.join(table_file);
+        info!("Processing {}", table_file.display());
+        tables.push(load_table(&table_file)?);
+    }
+    let problems: Vec<TableProblem> = tables.iter().flat_map(validate_table).collect();
+    report_table_problems(&problems)?;
+
+    info!("Processing {} npcs...", module_def.npc_files.len());
+    let npcs = load_records(&root_dir, &module_def.npc_files)?;
+    info!("Processing {} items...", module_def.item_files.len());
+    let items = load_records(&root_dir, &module_def.item_files)?;
+    info!("Processing {} magic items...", module_def.magic_item_files.len());
+    let magic_items = load_records(&root_dir, &module_def.magic_item_files)?;
+    info!("Processing {} feats...", module_def.feat_files.len());
+    let feats = load_records(&root_dir, &module_def.feat_files)?;
+    info!("Processing {} backgrounds...", module_def.background_files.len());
+    let backgrounds = load_records(&root_dir, &module_def.background_files)?;
+    info!("Processing {} races...", module_def.race_files.len());
+    let races = load_records(&root_dir, &module_def.race_files)?;
+    info!("Processing {} classes...", module_def.class_files.len());
+    let classes = load_records(&root_dir, &module_def.class_files)?;
+    info!("Processing {} subclasses...", module_def.subclass_files.len());
+    let subclasses = load_records(&root_dir, &module_def.subclass_files)?;
+    info!("Processing {} encounters...", module_def.encounter_files.len());
+    let encounters = load_records(&root_dir, &module_def.encounter_files)?;
+    info!("Processing {} parcels...", module_def.parcel_files.len());
+    let parcels = load_records(&root_dir, &module_def.parcel_files)?;
+    info!("Processing {} quests...", module_def.quest_files.len());
+    let quests = load_records(&root_dir, &module_def.quest_files)?;
+
+    info!("Processing {} story categories...", module_def.story_files.len());
+    for story_category in &module_def.story_files {
+        let mut story_files: Vec<PathBuf> = story_category
+            .files
+            .iter()
+            .map(|story_file| root_dir.join(story_file))
+            .collect();
+
+        if let Some(directory) = &story_category.directory {
+            let mut directory_files: Vec<PathBuf> =
+                std::fs::read_dir(root_dir.join(directory))?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some("md"))
+                    .collect();
+            directory_files.sort();
+            story_files.extend(directory_files);
+        }
 
-        let module_def: ModuleDefinition =
-            serde_yaml::from_str(&std::fs::read_to_string(&self.module_definition)?)?;
+        for story_file in story_files {
+            info!("Processing {}", story_file.display());
+            let file_name = story_file.file_stem().unwrap().to_string_lossy();
+            let story = StoryDefinition::from_markdown(
+                &story_category.category,
+                &file_name,
+                &std::fs::read_to_string(&story_file)?,
+            );
+            stories.push(story);
+        }
+    }
 
-        info!("Processing {} spells...", module_def.spell_files.len());
-        for spell_file in &module_def.spell_files {
-            let spell_file = root_dir.join(spell_file);
-            let spell_file = spell_file.to_str().unwrap();
-            info!("Processing {}", spell_file);
-            let spell: SpellDefinition =
-                serde_yaml::from_str(&std::fs::read_to_string(&spell_file)?)?;
-            spells.push(spell);
+    info!("Processing {} images...", module_def.images.len());
+    for image in &mut module_def.images {
+        image.path = root_dir.join(&image.file);
+        info!("Processing {}", image.path.display());
+        let extension = image
+            .path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        match extension.as_deref() {
+            Some("png") | Some("jpg") | Some("jpeg") => {}
+            _ => anyhow::bail!("Image {} must be a PNG or JPG file", image.file),
         }
+        if !image.path.is_file() {
+            anyhow::bail!("Image {} does not exist", image.file);
+        }
+    }
 
-        info!("Processing {} tables...", module_def.table_files.len());
-        for table_file in &module_def.table_files {
-            let table_file = root_dir.join(table_file);
-            let table_file = table_file.to_str().unwrap();
-            info!("Processing {}", table_file);
-            let table: TableDefinition =
-                serde_yaml::from_str(&std::fs::read_to_string(&table_file)?)?;
-            tables.push(table);
+    let mut reference_manual: Option<ReferenceManual> = None;
+    if let Some(manual_def) = &module_def.reference_manual {
+        info!("Processing {} reference manual chapters...", manual_def.chapters.len());
+        let mut chapters: Vec<ReferenceChapter> = Vec::new();
+        for chapter_file in &manual_def.chapters {
+            let chapter_file = root_dir.join(chapter_file);
+            info!("Processing {}", chapter_file.display());
+            let chapter = ReferenceChapter::from_markdown(
+                &chapter_file,
+                &std::fs::read_to_string(&chapter_file)?,
+            );
+            chapters.push(chapter);
         }
+        reference_manual = Some(ReferenceManual {
+            name: manual_def
+                .name
+                .clone()
+                .unwrap_or_else(|| module_def.name.clone()),
+            chapters,
+        });
+    }
+
+    let mut tokens: Vec<TokenFile> = Vec::new();
+    for token_dir in &module_def.tokens {
+        info!("Processing tokens in {}", token_dir);
+        let token_dir = root_dir.join(token_dir);
+        collect_tokens(&token_dir, &token_dir, &mut tokens)?;
+    }
+
+    let thumbnail = match &module_def.thumbnail {
+        Some(thumbnail) => Some(std::fs::read(root_dir.join(thumbnail))?),
+        None => None,
+    };
+
+    Ok(super::output::FGUModule {
+        module: module_def,
+        spells,
+        tables,
+        npcs,
+        items,
+        magic_items,
+        feats,
+        backgrounds,
+        races,
+        classes,
+        subclasses,
+        encounters,
+        parcels,
+        quests,
+        stories,
+        reference_manual,
+        thumbnail,
+        tokens,
+    })
+}
+
+#[derive(Clap, Debug)]
+pub struct BuildTokensArgs {
+    /// Directory containing the token images
+    #[clap(long = "input", short = 'i')]
+    pub input_dir: String,
+
+    /// Name of your token pack
+    #[clap(long = "name")]
+    pub name: String,
+
+    /// Author of the token pack
+    #[clap(long = "author", default_value = "Unknown")]
+    pub author: String,
+
+    /// Where to write the module file to
+    #[clap(long = "output", short = 'o')]
+    pub output: String,
+}
 
-        let fgu_module = super::output::FGUModule {
-            module: module_def,
-            spells,
-            tables,
+#[async_trait]
+impl CommandExec for BuildTokensArgs {
+    async fn exec(&self) -> Result<(), anyhow::Error> {
+        let module_def = ModuleDefinition {
+            name: self.name.clone(),
+            tokens: vec![self.input_dir.clone()],
+            source: self.name.clone(),
+            category: ModuleCategory::Tokens,
+            author: self.author.clone(),
+            ..Default::default()
         };
 
+        let mut tokens: Vec<TokenFile> = Vec::new();
+        let token_dir = PathBuf::from(&self.input_dir);
+        collect_tokens(&token_dir, &token_dir, &mut tokens)?;
+        if tokens.is_empty() {
+            anyhow::bail!("No PNG or JPG tokens found in {}", self.input_dir);
+        }
+        info!("Packaging {} tokens...", tokens.len());
+
+        let mut fgu_module = super::output::FGUModule::new(module_def);
+        fgu_module.tokens = tokens;
         fgu_module.process(&self.output)?;
 
         Ok(())
     }
 }
+
+fn collect_tokens(
+    root_dir: &Path,
+    dir: &Path,
+    tokens: &mut Vec<TokenFile>,
+) -> Result<(), anyhow::Error> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if path.is_dir() {
+            collect_tokens(root_dir, &path, tokens)?;
+            continue;
+        }
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        if !matches!(extension.as_deref(), Some("png") | Some("jpg") | Some("jpeg")) {
+            continue;
+        }
+
+        let relative_path: Vec<String> = path
+            .strip_prefix(root_dir)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().to_string())
+            .collect();
+        let archive_path = format!("tokens/{}", relative_path.join("/"));
+
+        let bytes = std::fs::read(&path)?;
+        match super::output::image_size(&bytes) {
+            Some((width, height)) if width != height => {
+                warn!("Token {} is {}x{}, FGU expects square tokens", archive_path, width, height)
+            }
+            None => warn!("Unable to read the size of token {}", archive_path),
+            _ => {}
+        }
+
+        tokens.push(TokenFile { path, archive_path });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_collected_from_every_folder() {
+        let dir = std::env::temp_dir().join(format!("fguctl-tokens-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("goblins")).unwrap();
+        std::fs::write(dir.join("wolf.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+        std::fs::write(dir.join("goblins").join("boss.JPG"), [0xFF, 0xD8]).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not a token").unwrap();
+
+        let mut tokens = Vec::new();
+        let collected = collect_tokens(&dir, &dir, &mut tokens);
+        std::fs::remove_dir_all(&dir).unwrap();
+        collected.unwrap();
+
+        let paths: Vec<&str> = tokens
+            .iter()
+            .map(|token| token.archive_path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["tokens/goblins/boss.JPG", "tokens/wolf.png"]);
+    }
+}
@@ -0,0 +1,320 @@
+use super::{RecordNames, XmlContent, XmlElement};
+
+/// Turns FGU formatted text back into the Markdown it could have been built from.
+/// Markdown has no underline, so underlined text comes back plain.
+pub fn to_markdown(element: &XmlElement, records: &RecordNames) -> String {
+    // text without any markup is kept as it is, line breaks and all
+    if element.elements().next().is_none() {
+        return element
+            .text()
+            .lines()
+            .map(|line| escape_line_start(&escape(line.trim_end())))
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_owned();
+    }
+
+    let mut blocks = Vec::new();
+    let mut loose = String::new();
+
+    for content in &element.children {
+        let child = match content {
+            XmlContent::Text(text) => {
+                loose.push_str(&escape(&collapse_whitespace(text)));
+                continue;
+            }
+            XmlContent::Element(child) => child,
+        };
+        let block = match child.name.as_str() {
+            "p" => inline(child, records),
+            "h" | "h1" => format!("# {}", inline(child, records)),
+            "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = child.name[1..].parse().unwrap_or(2);
+                format!("{} {}", "#".repeat(level), inline(child, records))
+            }
+            "list" | "ul" => list(child, records, |_| "-".to_owned()),
+            "ol" => list(child, records, |index| format!("{}.", index + 1)),
+            "linklist" => child
+                .elements()
+                .map(|link| link_reference(link, records))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "table" => table(child, records),
+            "frame" | "blockquote" => to_markdown(child, records)
+                .lines()
+                .map(|line| format!("> {}", line).trim_end().to_owned())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            "pre" => format!("```\n{}\n```", child.text().trim_end()),
+            "hr" => "---".to_owned(),
+            _ => {
+                loose.push_str(&inline_content(child, records));
+                continue;
+            }
+        };
+        push_block(&mut blocks, &mut loose);
+        if !block.trim().is_empty() {
+            blocks.push(block);
+        }
+    }
+    push_block(&mut blocks, &mut loose);
+
+    blocks.join("\n\n")
+}
+
+fn push_block(blocks: &mut Vec<String>, loose: &mut String) {
+    let text = loose
+        .trim()
+        .lines()
+        .map(|line| escape_line_start(line.trim()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !text.is_empty() {
+        blocks.push(text);
+    }
+    loose.clear();
+}
+
+fn list<F>(element: &XmlElement, records: &RecordNames, marker: F) -> String
+where
+    F: Fn(usize) -> String,
+{
+    element
+        .elements()
+        .filter(|item| item.name == "li")
+        .enumerate()
+        .map(|(index, item)| format!("{} {}", marker(index), inline(item, records)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn table(element: &XmlElement, records: &RecordNames) -> String {
+    let rows: Vec<Vec<String>> = element
+        .elements()
+        .flat_map(|row| {
+            // rows may sit directly in the table or inside thead/tbody
+            if row.name == "tr" {
+                vec![row]
+            } else {
+                row.elements().filter(|row| row.name == "tr").collect()
+            }
+        })
+        .map(|row| {
+            row.elements()
+                .map(|cell| inline(cell, records).replace('|', "\\|"))
+                .collect()
+        })
+        .collect();
+    let columns = rows.iter().map(Vec::len).max().unwrap_or_default();
+    if columns == 0 {
+        return String::new();
+    }
+
+    let line = |cells: &[String]| {
+        let mut cells = cells.to_vec();
+        cells.resize(columns, String::new());
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = vec![line(&rows[0]), line(&vec!["---".to_owned(); columns])];
+    lines.extend(rows[1..].iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+fn link_reference(link: &XmlElement, records: &RecordNames) -> String {
+    let reference = match (link.attribute("class"), link.attribute("recordname")) {
+        (Some(class), Some(record_name)) => records.resolve(class, record_name),
+        _ => None,
+    };
+    match reference {
+        Some(reference) => format!("[[{}:{}]]", reference.record_type, reference.name),
+        None => link.text().trim().to_owned(),
+    }
+}
+
+/// The text of a paragraph-like element with inline formatting as Markdown.
+fn inline(element: &XmlElement, records: &RecordNames) -> String {
+    inline_children(element, records)
+        .lines()
+        .map(|line| escape_line_start(line.trim()))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+fn inline_children(element: &XmlElement, records: &RecordNames) -> String {
+    element
+        .children
+        .iter()
+        .map(|content| match content {
+            XmlContent::Text(text) => escape(&collapse_whitespace(text)),
+            XmlContent::Element(child) => inline_content(child, records),
+        })
+        .collect()
+}
+
+fn inline_content(element: &XmlElement, records: &RecordNames) -> String {
+    let text = inline_children(element, records);
+    match element.name.as_str() {
+        "b" | "strong" => wrap(&text, "**"),
+        "i" | "em" => wrap(&text, "*"),
+        "code" => wrap(&collapse_whitespace(&element.text()), "`"),
+        "a" => match element.attribute("href") {
+            Some(href) => format!("[{}]({})", text.trim(), href),
+            None => text,
+        },
+        "link" => link_reference(element, records),
+        "br" => "\n".to_owned(),
+        _ => text,
+    }
+}
+
+/// Wraps text in a Markdown marker, keeping surrounding spaces outside of it.
+fn wrap(text: &str, marker: &str) -> String {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return text.to_owned();
+    }
+    let leading = if text.starts_with(' ') { " " } else { "" };
+    let trailing = if text.ends_with(' ') { " " } else { "" };
+    format!("{}{}{}{}{}", leading, marker, trimmed, marker, trailing)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_whitespace = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_whitespace {
+                collapsed.push(' ');
+            }
+            in_whitespace = true;
+        } else {
+            collapsed.push(c);
+            in_whitespace = false;
+        }
+    }
+    collapsed
+}
+
+/// Escapes the characters that would turn plain text into Markdown formatting: code
+/// spans, emphasis that could open or close, and anything that could start a link.
+fn escape(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut escaped = String::with_capacity(text.len());
+
+    for (index, c) in chars.iter().copied().enumerate() {
+        let before = index.checked_sub(1).map(|before| chars[before]);
+        let after = chars.get(index + 1).copied();
+        let is_space = |c: Option<char>| c.map_or(true, char::is_whitespace);
+        let is_word = |c: Option<char>| c.map_or(false, char::is_alphanumeric);
+        let needs_escape = match c {
+            '\\' => after.map_or(false, |after| after.is_ascii_punctuation()),
+            '`' => true,
+            // a lone `*` between spaces is just an asterisk, `snake_case` isn't emphasis
+            '*' => !(is_space(before) && is_space(after)),
+            '_' => !(is_space(before) && is_space(after) || is_word(before) && is_word(after)),
+            '[' => after == Some('[') || chars[index..].windows(2).any(|pair| pair == [']', '(']),
+            _ => false,
+        };
+        if needs_escape {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+/// Escapes what would make a line of text a heading, quote, list item or rule.
+fn escape_line_start(line: &str) -> String {
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        return format!("{}\\{}", &line[..digits], &line[digits..]);
+    }
+    let starts_block = line.starts_with('#')
+        || line.starts_with('>')
+        || line.starts_with(&['-', '='][..]) && line.chars().all(|c| "-= ".contains(c))
+        || ["- ", "+ ", "* "]
+            .iter()
+            .any(|marker| line.starts_with(marker));
+    if starts_block {
+        format!("\\{}", line)
+    } else {
+        line.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(xml: &str) -> String {
+        let client = XmlElement::parse(
+            "<root><spell><id-00001><name type=\"string\">Fireball</name></id-00001></spell></root>",
+        )
+        .unwrap();
+        let text = XmlElement::parse(xml).unwrap();
+        to_markdown(&text, &RecordNames::new(&client))
+    }
+
+    #[test]
+    fn formatting_becomes_markdown() {
+        let xml = "<formattedtext>\
+            <h>Fireball</h>\
+            <p>A <b>bright</b> streak, <i>roll</i> on \
+            <link class=\"reference_spell\" recordname=\"spell.id-00001@Module\">Fireball</link>.</p>\
+            <list><li>First</li><li>Second</li></list>\
+            </formattedtext>";
+        assert_eq!(
+            markdown(xml),
+            "# Fireball\n\n\
+             A **bright** streak, *roll* on [[spell:Fireball]].\n\n\
+             - First\n- Second"
+        );
+    }
+
+    #[test]
+    fn underline_is_not_turned_into_emphasis() {
+        assert_eq!(
+            markdown("<formattedtext><p>An <u>underlined</u> word</p></formattedtext>"),
+            "An underlined word"
+        );
+    }
+
+    #[test]
+    fn literal_markdown_characters_are_escaped() {
+        let xml = "<formattedtext>\
+            <p>Roll 2d6*10 gold, see [[notes]] or [this](page)</p>\
+            <p># not a heading</p>\
+            <p>- not a list</p>\
+            <p>1. not a list either</p>\
+            <p>a_b stays, _this_ and `that` do not</p>\
+            </formattedtext>";
+        assert_eq!(
+            markdown(xml),
+            "Roll 2d6\\*10 gold, see \\[\\[notes]] or \\[this](page)\n\n\
+             \\# not a heading\n\n\
+             \\- not a list\n\n\
+             1\\. not a list either\n\n\
+             a_b stays, \\_this\\_ and \\`that\\` do not"
+        );
+    }
+
+    #[test]
+    fn plain_text_is_escaped_line_by_line() {
+        assert_eq!(
+            markdown("<text>* one\n2 * 3\n---</text>"),
+            "\\* one\n2 * 3\n\\---"
+        );
+    }
+
+    #[test]
+    fn code_is_kept_as_written() {
+        assert_eq!(
+            markdown("<formattedtext><p>Type <code>a_b*c</code></p></formattedtext>"),
+            "Type `a_b*c`"
+        );
+    }
+}
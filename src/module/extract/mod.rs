@@ -0,0 +1,323 @@
+use crate::module::inputs::*;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+use tracing::warn;
+
+mod markdown;
+mod spell;
+mod tables;
+
+/// The YAML inputs read back out of a built module.
+pub struct ExtractedModule {
+    pub module: ModuleDefinition,
+    pub spells: Vec<SpellDefinition>,
+    pub tables: Vec<TableDefinition>,
+}
+
+/// Opens a `.mod` file and turns its spells and tables back into definitions.
+pub fn extract_module(path: &Path) -> Result<ExtractedModule, anyhow::Error> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let definition = XmlElement::parse(&read_entry(&mut archive, "definition.xml")?)?;
+    let client = XmlElement::parse(&read_entry(&mut archive, "client.xml")?)?;
+    let records = RecordNames::new(&client);
+
+    let mut spells = Vec::new();
+    let mut tables = Vec::new();
+    for node in client.elements() {
+        match node.name.as_str() {
+            "spell" => {
+                for record in node.records() {
+                    spells.push(SpellDefinition::extract(record, &records)?);
+                }
+            }
+            "tables" => {
+                for record in node.records() {
+                    tables.push(TableDefinition::extract(record, &records)?);
+                }
+            }
+            "library" => {}
+            other => {
+                let count = node.records().len();
+                if count > 0 {
+                    warn!(
+                        "Skipping {} {} records, only spells and tables can be extracted",
+                        count, other
+                    );
+                }
+            }
+        }
+    }
+
+    let name = definition.child_text("name").unwrap_or_default();
+    let category = match definition.child_text("category").as_deref() {
+        Some("Tokens") => ModuleCategory::Tokens,
+        _ => ModuleCategory::SourceBook,
+    };
+    let module = ModuleDefinition {
+        source: name.clone(),
+        name,
+        category,
+        author: definition.child_text("author").unwrap_or_default(),
+        ..Default::default()
+    };
+
+    Ok(ExtractedModule {
+        module,
+        spells,
+        tables,
+    })
+}
+
+fn read_entry<R>(archive: &mut zip::ZipArchive<R>, name: &str) -> Result<String, anyhow::Error>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    let mut contents = String::new();
+    archive
+        .by_name(name)
+        .map_err(|error| anyhow::anyhow!("Module has no {}: {}", name, error))?
+        .read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+pub enum XmlContent {
+    Element(XmlElement),
+    Text(String),
+}
+
+/// A parsed XML element, keeping text and child elements in document order so
+/// formatted text can be turned back into Markdown.
+pub struct XmlElement {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<XmlContent>,
+}
+
+impl XmlElement {
+    pub fn parse(xml: &str) -> Result<Self, anyhow::Error> {
+        let mut reader = Reader::from_str(xml);
+        let mut buf = Vec::new();
+        let mut stack = vec![XmlElement {
+            name: String::new(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+        }];
+
+        loop {
+            let content = match reader.read_event(&mut buf)? {
+                // HTML's void elements are sometimes left unclosed
+                Event::Start(start) if is_void_element(start.name()) => Some(
+                    XmlContent::Element(XmlElement::from_start(&start, &reader)?),
+                ),
+                Event::Start(start) => {
+                    stack.push(XmlElement::from_start(&start, &reader)?);
+                    None
+                }
+                Event::Empty(start) => Some(XmlContent::Element(XmlElement::from_start(
+                    &start, &reader,
+                )?)),
+                Event::End(end) if is_void_element(end.name()) => None,
+                Event::End(_) => {
+                    if stack.len() < 2 {
+                        anyhow::bail!("Unexpected closing tag in XML");
+                    }
+                    stack.pop().map(XmlContent::Element)
+                }
+                Event::Text(text) => Some(XmlContent::Text(
+                    text.unescape_and_decode(&reader)
+                        .unwrap_or_else(|_| String::from_utf8_lossy(&text).into_owned()),
+                )),
+                Event::CData(text) => Some(XmlContent::Text(
+                    String::from_utf8_lossy(&text).into_owned(),
+                )),
+                Event::Eof => break,
+                _ => None,
+            };
+            if let (Some(content), Some(parent)) = (content, stack.last_mut()) {
+                parent.children.push(content);
+            }
+            buf.clear();
+        }
+
+        stack
+            .into_iter()
+            .next()
+            .and_then(|document| {
+                document
+                    .children
+                    .into_iter()
+                    .find_map(|content| match content {
+                        XmlContent::Element(element) => Some(element),
+                        XmlContent::Text(_) => None,
+                    })
+            })
+            .ok_or_else(|| anyhow::anyhow!("XML document has no root element"))
+    }
+
+    fn from_start(start: &BytesStart, reader: &Reader<&[u8]>) -> Result<Self, anyhow::Error> {
+        let mut attributes = Vec::new();
+        for attribute in start.attributes() {
+            let attribute = attribute?;
+            attributes.push((
+                String::from_utf8_lossy(attribute.key).into_owned(),
+                attribute.unescape_and_decode_value(reader)?,
+            ));
+        }
+
+        Ok(Self {
+            name: String::from_utf8_lossy(start.name()).into_owned(),
+            attributes,
+            children: Vec::new(),
+        })
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &XmlElement> {
+        self.children.iter().filter_map(|content| match content {
+            XmlContent::Element(element) => Some(element),
+            XmlContent::Text(_) => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&XmlElement> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// All text inside the element, including that of its children.
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .map(|content| match content {
+                XmlContent::Element(element) => element.text(),
+                XmlContent::Text(text) => text.clone(),
+            })
+            .collect()
+    }
+
+    /// The trimmed text of a child element, `None` if it's missing or empty.
+    pub fn child_text(&self, name: &str) -> Option<String> {
+        let text = self.child(name)?.text().trim().to_owned();
+        if text.is_empty() {
+            None
+        } else {
+            Some(text)
+        }
+    }
+
+    pub fn child_number<T>(&self, name: &str) -> Option<T>
+    where
+        T: std::str::FromStr,
+    {
+        self.child_text(name)?.parse().ok()
+    }
+
+    /// The number of an `id-00001` element.
+    pub fn record_id(&self) -> Option<u32> {
+        self.name.strip_prefix("id-")?.parse().ok()
+    }
+
+    /// The `id-*` records of a node, looking inside any `category` elements.
+    pub fn records(&self) -> Vec<&XmlElement> {
+        let mut records = Vec::new();
+        for element in self.elements() {
+            if element.record_id().is_some() {
+                records.push(element);
+            } else if element.name == "category" {
+                records.extend(element.records());
+            }
+        }
+        records
+    }
+}
+
+fn is_void_element(name: &[u8]) -> bool {
+    matches!(name, b"br" | b"hr" | b"img")
+}
+
+/// Knows the name of every record in the module, so links can be turned back
+/// into `[[type:Name]]` references.
+pub struct RecordNames {
+    names: HashMap<String, String>,
+}
+
+impl RecordNames {
+    pub fn new(client: &XmlElement) -> Self {
+        let mut names = HashMap::new();
+        for node in client.elements() {
+            if node.name == "library" {
+                continue;
+            }
+            for record in node.records() {
+                if let Some(name) = record.child_text("name") {
+                    names.insert(format!("{}.{}", node.name, record.name), name);
+                }
+            }
+        }
+        Self { names }
+    }
+
+    /// Resolves a link such as `spell.id-00001@Module` opened with `class`.
+    pub fn resolve(&self, class: &str, record_name: &str) -> Option<RecordReference> {
+        let path = record_name.split('@').next()?;
+        Some(RecordReference {
+            record_type: record_type(class)?.to_owned(),
+            name: self.names.get(path)?.clone(),
+        })
+    }
+}
+
+/// Maps the window class of a link back to the record type used in `[[type:Name]]`.
+fn record_type(class: &str) -> Option<&'static str> {
+    let record_type = match class {
+        "reference_spell" => "spell",
+        "table" => "table",
+        "npc" => "npc",
+        "item" => "item",
+        "reference_feat" => "feat",
+        "reference_background" => "background",
+        "reference_race" => "race",
+        "reference_class" => "class",
+        "reference_class_specialization" => "subclass",
+        "imagewindow" => "image",
+        "battle" => "encounter",
+        "treasureparcel" => "parcel",
+        "quest" => "quest",
+        "encounter" => "story",
+        _ => return None,
+    };
+    Some(record_type)
+}
+
+/// Turns a record name into a file name that isn't in `used` yet.
+pub fn file_name(name: &str, used: &mut Vec<String>) -> String {
+    let mut stem = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    if stem.is_empty() {
+        stem = "record".to_owned();
+    }
+
+    let mut file_name = format!("{}.yaml", stem);
+    let mut counter = 1;
+    while used.contains(&file_name) {
+        counter += 1;
+        file_name = format!("{}_{}.yaml", stem, counter);
+    }
+    used.push(file_name.clone());
+    file_name
+}
@@ -0,0 +1,176 @@
+use crate::module::inputs::*;
+use super::markdown::to_markdown;
+use super::{RecordNames, XmlElement};
+use tracing::warn;
+
+impl SpellDefinition {
+    pub fn extract(record: &XmlElement, records: &RecordNames) -> Result<Self, anyhow::Error> {
+        let name = record.child_text("name").unwrap_or_default();
+        let casting_time = record.child_text("castingtime").unwrap_or_default();
+        let casting_time = SpellCastDuration::parse(&casting_time).unwrap_or_else(|| {
+            warn!(
+                "Spell {} has an unknown casting time '{}', using 1 action",
+                name, casting_time
+            );
+            SpellCastDuration::Action { count: 1 }
+        });
+        let spell_level = match record.child_number("level").unwrap_or(0) {
+            0 => SpellLevel::Cantrip,
+            number => SpellLevel::Level { number },
+        };
+
+        Ok(Self {
+            id: RecordId::extracted(record.record_id().unwrap_or_default(), &name),
+            short_description: record.child_text("shortdescription"),
+            description: record
+                .child("description")
+                .map(|description| to_markdown(description, records))
+                .unwrap_or_default(),
+            duration: record.child_text("duration"),
+            components: record.child_text("components"),
+            casting_time,
+            school: record.child_text("school").unwrap_or_default(),
+            spell_level,
+            needs_preperation: record.child_number("prepared").unwrap_or(0) != 0,
+            is_ritual: record.child_number("ritual").unwrap_or(0) != 0,
+            group: record.child_text("group").unwrap_or_default(),
+            actions: record
+                .child("actions")
+                .map(|actions| SpellActions::extract(actions, &name))
+                .unwrap_or_default(),
+            name,
+        })
+    }
+}
+
+impl SpellActions {
+    pub fn extract(actions: &XmlElement, name: &str) -> Self {
+        let mut entries: Vec<&XmlElement> = actions.elements().collect();
+        entries.sort_by_key(|action| action.child_number::<u32>("order").unwrap_or_default());
+
+        let mut result = SpellActions::default();
+        for action in entries {
+            match action.child_text("type").as_deref() {
+                Some("cast") => {
+                    if let Some(range) = action.child_text("atktype") {
+                        let range = match range.as_str() {
+                            "ranged" => AttackRange::Ranged,
+                            _ => AttackRange::Melee,
+                        };
+                        result.attacks.push(SpellRange {
+                            range,
+                            save: ToSave::DC,
+                        });
+                    }
+                    if let Some(ability) = ability(action, "savetype") {
+                        result.saves.push(SpellSave {
+                            is_magic: action.child_number("savemagic").unwrap_or(0) != 0,
+                            stat: SpellStat::AbilityScore { ability },
+                            save: extract_save(action),
+                        });
+                    }
+                }
+                Some("damage") => result.damages.push(extract_damage(action)),
+                Some("effect") => result.effects.push(extract_effect(action, name)),
+                other => warn!(
+                    "Spell {} has an action of unknown type {:?}, skipping it",
+                    name, other
+                ),
+            }
+        }
+        result
+    }
+}
+
+fn ability(element: &XmlElement, name: &str) -> Option<AbilityScore> {
+    AbilityScore::from_long_name(&element.child_text(name)?)
+}
+
+fn extract_save(action: &XmlElement) -> ToSave {
+    match action.child_text("savedcbase").as_deref() {
+        Some("ability") => {}
+        Some("fixed") => {
+            return ToSave::Fixed {
+                value: action.child_number("savedcmod").unwrap_or(0),
+            }
+        }
+        _ => return ToSave::DC,
+    }
+
+    ToSave::Ability(CustomSpellSave {
+        stat: SpellStat::AbilityScore {
+            ability: ability(action, "savedcstat").unwrap_or(AbilityScore::Wisdom),
+        },
+        is_proficient: action.child_number("savedcprof").unwrap_or(0) != 0,
+        bonus: action.child_number("savedcmod").unwrap_or(0),
+    })
+}
+
+fn extract_damage(action: &XmlElement) -> ActionDamage {
+    let entries = match action.child("damagelist") {
+        Some(list) => list.elements().collect(),
+        None => Vec::new(),
+    };
+
+    let damage = entries
+        .into_iter()
+        .map(|entry| {
+            // FGU's own records list the dice one by one, so d6,d6 goes back to 2d6
+            let mut dice: Vec<Dice> = Vec::new();
+            let text = entry.child_text("dice").unwrap_or_default();
+            for term in text.split(',').map(str::trim) {
+                let parsed = match Dice::parse_expression(term) {
+                    Some(parsed) => parsed,
+                    None => continue,
+                };
+                for next in parsed {
+                    match dice.last_mut() {
+                        Some(last) if term.starts_with('d') && last.dice_type == next.dice_type => {
+                            last.count += next.count
+                        }
+                        _ => dice.push(next),
+                    }
+                }
+            }
+            SpellDamage {
+                modifier: match ability(entry, "stat") {
+                    Some(ability) => DamageModifier::AbilityScore { ability },
+                    None => DamageModifier::None,
+                },
+                damage_type: entry.child_text("type").unwrap_or_default(),
+                dice,
+                bonus: entry.child_number("bonus").filter(|bonus| *bonus != 0),
+            }
+        })
+        .collect();
+
+    ActionDamage { damage }
+}
+
+fn extract_effect(action: &XmlElement, name: &str) -> SpellEffect {
+    // FGU counts in rounds unless the effect says otherwise
+    let unit = match action.child_text("durunit").as_deref() {
+        Some("round") | None => Some(TimeUnit::Round),
+        Some("minute") => Some(TimeUnit::Minute),
+        Some("hour") => Some(TimeUnit::Hour),
+        Some(other) => {
+            warn!(
+                "Spell {} has an effect lasting {}s, which can't be expressed, making it indefinite",
+                name, other
+            );
+            None
+        }
+    };
+    let duration = match (unit, action.child_number("durmod")) {
+        (Some(unit), Some(count)) if count > 0 => {
+            SpellEffectDuration::Finite(SpellEffectDurationFinite { count, unit })
+        }
+        _ => SpellEffectDuration::Indefinite,
+    };
+
+    SpellEffect {
+        effect: action.child_text("label").unwrap_or_default(),
+        duration,
+        targets_self: action.child_text("targeting").as_deref() == Some("self"),
+    }
+}
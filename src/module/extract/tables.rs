@@ -0,0 +1,125 @@
+use crate::module::inputs::*;
+use super::markdown::to_markdown;
+use super::{RecordNames, XmlElement};
+use std::path::PathBuf;
+use tracing::warn;
+
+impl TableDefinition {
+    pub fn extract(record: &XmlElement, records: &RecordNames) -> Result<Self, anyhow::Error> {
+        let name = record.child_text("name").unwrap_or_default();
+        let formatted_text = record
+            .child("notes")
+            .map(|notes| to_markdown(notes, records))
+            .filter(|notes| !notes.is_empty());
+
+        let column_count = record.child_number("resultscols").unwrap_or(1).max(1);
+        let mut columns: Vec<String> = (1..=column_count)
+            .map(|index| {
+                record
+                    .child_text(&format!("labelcol{}", index))
+                    .unwrap_or_default()
+            })
+            .collect();
+        // a lone "Effect" column is what an empty list of columns builds
+        if columns.len() == 1 && columns[0] == "Effect" {
+            columns.clear();
+        }
+
+        let mut ranges = Vec::new();
+        if let Some(rows) = record.child("tablerows") {
+            for row in rows.records() {
+                ranges.push(extract_range(row, &name, &columns, records)?);
+            }
+        }
+
+        Ok(Self {
+            id: RecordId::extracted(record.record_id().unwrap_or_default(), &name),
+            description: record.child_text("description").unwrap_or_default(),
+            formatted_text,
+            dice: record.child_text("dice").map(|dice| dice_expression(&dice)),
+            modifier: record.child_number("mod").unwrap_or(0),
+            columns,
+            ranges,
+            path: PathBuf::new(),
+            name,
+        })
+    }
+}
+
+fn extract_range(
+    row: &XmlElement,
+    table: &str,
+    columns: &[String],
+    records: &RecordNames,
+) -> Result<TableRange, anyhow::Error> {
+    let from = row.child_number("fromrange");
+    let until = row.child_number("torange");
+    let (from, until) = match (from, until) {
+        (Some(from), Some(until)) => (from, until),
+        _ => anyhow::bail!("Table {} has a row without a readable range", table),
+    };
+
+    let mut results = Vec::new();
+    let mut link = None;
+    if let Some(cells) = row.child("results") {
+        for (index, cell) in cells.records().into_iter().enumerate() {
+            results.push(cell.child_text("result").unwrap_or_default());
+            let reference = cell.child("resultlink").and_then(|resultlink| {
+                records.resolve(
+                    &resultlink.child_text("class")?,
+                    &resultlink.child_text("recordname")?,
+                )
+            });
+            match reference {
+                Some(reference) if index == 0 => link = Some(reference),
+                Some(reference) => warn!(
+                    "Table {} links {} from column {}, only the first column can link",
+                    table,
+                    reference.name,
+                    index + 1
+                ),
+                None => {}
+            }
+        }
+    }
+
+    // single column tables keep their result in the description
+    let description = if columns.len() <= 1 && results.len() == 1 {
+        results.remove(0)
+    } else {
+        String::new()
+    };
+
+    Ok(TableRange {
+        from,
+        until,
+        description,
+        results,
+        link,
+        line: 0,
+    })
+}
+
+/// Turns FGU's `d6,d6` back into `2d6`.
+fn dice_expression(text: &str) -> String {
+    let mut dice: Vec<(u32, &str)> = Vec::new();
+    for die in text.split(',').map(str::trim).filter(|die| !die.is_empty()) {
+        let (count, sides) = match die.find('d') {
+            Some(index) => die.split_at(index),
+            None => ("", die),
+        };
+        let count = count.parse().unwrap_or(1);
+        match dice.last_mut() {
+            Some(last) if last.1 == sides => last.0 += count,
+            _ => dice.push((count, sides)),
+        }
+    }
+
+    dice.iter()
+        .map(|(count, sides)| match count {
+            1 => sides.to_string(),
+            count => format!("{}{}", count, sides),
+        })
+        .collect::<Vec<_>>()
+        .join("+")
+}
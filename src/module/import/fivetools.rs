@@ -0,0 +1,616 @@
+use crate::module::inputs::*;
+use super::{casting_time, damage_dice, damage_type, spell_level, spell_saves};
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize)]
+struct FiveToolsFile {
+    #[serde(default)]
+    spell: Vec<FiveToolsSpell>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FiveToolsSpell {
+    name: String,
+    level: u8,
+    school: String,
+    #[serde(default)]
+    time: Vec<FiveToolsTime>,
+    #[serde(default)]
+    components: FiveToolsComponents,
+    #[serde(default)]
+    duration: Vec<FiveToolsDuration>,
+    #[serde(default)]
+    meta: FiveToolsMeta,
+    #[serde(default)]
+    entries: Vec<Value>,
+    #[serde(default)]
+    entries_higher_level: Vec<Value>,
+    #[serde(default)]
+    damage_inflict: Vec<String>,
+    #[serde(default)]
+    saving_throw: Vec<String>,
+    #[serde(default)]
+    spell_attack: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct FiveToolsTime {
+    number: u8,
+    unit: String,
+}
+
+#[derive(Default, Deserialize)]
+struct FiveToolsComponents {
+    #[serde(default)]
+    v: bool,
+    #[serde(default)]
+    s: bool,
+    m: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct FiveToolsDuration {
+    #[serde(rename = "type")]
+    duration_type: String,
+    duration: Option<FiveToolsAmount>,
+    #[serde(default)]
+    concentration: bool,
+    #[serde(default)]
+    ends: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FiveToolsAmount {
+    #[serde(rename = "type")]
+    unit: String,
+    #[serde(default)]
+    amount: u32,
+    #[serde(default)]
+    up_to: bool,
+}
+
+#[derive(Default, Deserialize)]
+struct FiveToolsMeta {
+    #[serde(default)]
+    ritual: bool,
+}
+
+pub fn import_spells(contents: &str) -> Result<Vec<SpellDefinition>, anyhow::Error> {
+    let file: FiveToolsFile = serde_json::from_str(contents)?;
+    file.spell.iter().map(FiveToolsSpell::to_spell).collect()
+}
+
+impl FiveToolsSpell {
+    fn to_spell(&self) -> Result<SpellDefinition, anyhow::Error> {
+        let mut blocks = Vec::new();
+        for entry in self.entries.iter().chain(&self.entries_higher_level) {
+            entry_blocks(entry, &mut blocks);
+        }
+        let casting_time = match self.time.first() {
+            Some(time) => casting_time(&self.name, time.number, &time.unit)?,
+            None => SpellCastDuration::Action { count: 1 },
+        };
+
+        Ok(SpellDefinition {
+            id: RecordId::default(),
+            name: self.name.clone(),
+            short_description: None,
+            description: blocks.join("\n\n"),
+            duration: self.duration_text(),
+            components: self.components_text(),
+            casting_time,
+            school: school_name(&self.school),
+            spell_level: spell_level(self.level),
+            needs_preperation: false,
+            is_ritual: self.meta.ritual,
+            group: "Spells".to_owned(),
+            actions: SpellActions {
+                attacks: self
+                    .spell_attack
+                    .iter()
+                    .map(|attack| SpellRange {
+                        range: match attack.as_str() {
+                            "M" => AttackRange::Melee,
+                            _ => AttackRange::Ranged,
+                        },
+                        save: ToSave::DC,
+                    })
+                    .collect(),
+                saves: spell_saves(self.saving_throw.iter().map(String::as_str)),
+                damages: self.damage().into_iter().collect(),
+                effects: Vec::new(),
+            },
+        })
+    }
+
+    fn components_text(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if self.components.v {
+            parts.push("V".to_owned());
+        }
+        if self.components.s {
+            parts.push("S".to_owned());
+        }
+        match &self.components.m {
+            Some(Value::String(material)) => parts.push(format!("M ({})", strip_tags(material))),
+            Some(Value::Object(material)) => match material.get("text").and_then(Value::as_str) {
+                Some(text) => parts.push(format!("M ({})", strip_tags(text))),
+                None => parts.push("M".to_owned()),
+            },
+            Some(Value::Bool(true)) => parts.push("M".to_owned()),
+            _ => {}
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join(", "))
+        }
+    }
+
+    fn duration_text(&self) -> Option<String> {
+        let texts: Vec<String> = self
+            .duration
+            .iter()
+            .map(|duration| match duration.duration_type.as_str() {
+                "instant" => "Instantaneous".to_owned(),
+                "timed" => {
+                    let amount = match &duration.duration {
+                        Some(amount) => {
+                            let plural = if amount.amount == 1 { "" } else { "s" };
+                            let up_to = if amount.up_to { "up to " } else { "" };
+                            format!("{}{} {}{}", up_to, amount.amount, amount.unit, plural)
+                        }
+                        None => "a while".to_owned(),
+                    };
+                    if duration.concentration {
+                        format!(
+                            "Concentration, up to {}",
+                            amount.trim_start_matches("up to ")
+                        )
+                    } else {
+                        let mut amount = amount;
+                        if let Some(first) = amount.get_mut(..1) {
+                            first.make_ascii_uppercase();
+                        }
+                        amount
+                    }
+                }
+                "permanent" if duration.ends.iter().any(|end| end == "dispel") => {
+                    "Until dispelled".to_owned()
+                }
+                "permanent" => "Permanent".to_owned(),
+                _ => "Special".to_owned(),
+            })
+            .collect();
+
+        if texts.is_empty() {
+            None
+        } else {
+            Some(texts.join(" or "))
+        }
+    }
+
+    /// The `{@damage}` rolls of the first entry that has any, later entries usually
+    /// describe how the damage scales.
+    fn damage(&self) -> Option<ActionDamage> {
+        let mut texts = Vec::new();
+        for entry in &self.entries {
+            entry_texts(entry, &mut texts);
+        }
+
+        texts.iter().find_map(|text| {
+            let mut damage = Vec::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{@damage ") {
+                // an unterminated tag ends the scan, keeping the damage found before it
+                let end = match rest[start..].find('}') {
+                    Some(end) => start + end,
+                    None => break,
+                };
+                let (dice, bonus) = damage_dice(&rest[start + 9..end]);
+                rest = &rest[end + 1..];
+                if dice.is_empty() {
+                    continue;
+                }
+                let damage_type = rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(damage_type)
+                    .map(str::to_owned)
+                    .or_else(|| match self.damage_inflict.as_slice() {
+                        [only] => Some(only.clone()),
+                        _ => None,
+                    })
+                    .unwrap_or_default();
+                damage.push(SpellDamage {
+                    modifier: DamageModifier::None,
+                    damage_type,
+                    dice,
+                    bonus,
+                });
+            }
+
+            if damage.is_empty() {
+                None
+            } else {
+                Some(ActionDamage { damage })
+            }
+        })
+    }
+}
+
+fn school_name(code: &str) -> String {
+    match code {
+        "A" => "Abjuration",
+        "C" => "Conjuration",
+        "D" => "Divination",
+        "E" => "Enchantment",
+        "V" => "Evocation",
+        "I" => "Illusion",
+        "N" => "Necromancy",
+        "T" => "Transmutation",
+        other => other,
+    }
+    .to_owned()
+}
+
+/// Every string of an entry, in the order they appear.
+fn entry_texts(entry: &Value, texts: &mut Vec<String>) {
+    match entry {
+        Value::String(text) => texts.push(text.clone()),
+        Value::Array(entries) => entries.iter().for_each(|entry| entry_texts(entry, texts)),
+        Value::Object(fields) => {
+            for name in &["entry", "entries", "items"] {
+                if let Some(entry) = fields.get(*name) {
+                    entry_texts(entry, texts);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Turns an entry into Markdown blocks.
+fn entry_blocks(entry: &Value, blocks: &mut Vec<String>) {
+    let fields = match entry {
+        Value::String(text) => return blocks.push(strip_tags(text)),
+        Value::Object(fields) => fields,
+        _ => return,
+    };
+    let entries = fields
+        .get("entries")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    match fields.get("type").and_then(Value::as_str) {
+        Some("list") => {
+            let items = fields
+                .get("items")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let lines: Vec<String> = items
+                .iter()
+                .map(|item| {
+                    let mut item_blocks = Vec::new();
+                    entry_blocks(item, &mut item_blocks);
+                    format!("- {}", item_blocks.join(" "))
+                })
+                .collect();
+            blocks.push(lines.join("\n"));
+        }
+        Some("table") => blocks.push(table(fields)),
+        Some("quote") => {
+            let mut quoted = Vec::new();
+            entries
+                .iter()
+                .for_each(|entry| entry_blocks(entry, &mut quoted));
+            blocks.push(
+                quoted
+                    .iter()
+                    .map(|block| format!("> {}", block))
+                    .collect::<Vec<_>>()
+                    .join("\n>\n"),
+            );
+        }
+        _ => {
+            let first = blocks.len();
+            entries.iter().for_each(|entry| entry_blocks(entry, blocks));
+            if let Some(entry) = fields.get("entry") {
+                entry_blocks(entry, blocks);
+            }
+            // a named entry reads as a bold lead-in to its first paragraph
+            if let Some(name) = fields.get("name").and_then(Value::as_str) {
+                let name = format!("**{}.**", strip_tags(name));
+                match blocks.get_mut(first) {
+                    Some(block) => *block = format!("{} {}", name, block),
+                    None => blocks.push(name),
+                }
+            }
+        }
+    }
+}
+
+fn table(fields: &serde_json::Map<String, Value>) -> String {
+    let cell = |value: &Value| {
+        let mut cell_blocks = Vec::new();
+        entry_blocks(value, &mut cell_blocks);
+        cell_blocks.join(" ").replace('|', "\\|")
+    };
+    let labels: Vec<String> = fields
+        .get("colLabels")
+        .and_then(Value::as_array)
+        .map(|labels| labels.iter().map(cell).collect())
+        .unwrap_or_default();
+    let rows: Vec<Vec<String>> = fields
+        .get("rows")
+        .and_then(Value::as_array)
+        .map(|rows| {
+            rows.iter()
+                .filter_map(Value::as_array)
+                .map(|row| row.iter().map(cell).collect())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let columns = rows
+        .iter()
+        .map(Vec::len)
+        .chain(Some(labels.len()))
+        .max()
+        .unwrap_or(0);
+    let line = |cells: &[String]| {
+        let mut cells = cells.to_vec();
+        cells.resize(columns, String::new());
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = vec![line(&labels), line(&vec!["---".to_owned(); columns])];
+    lines.extend(rows.iter().map(|row| line(row)));
+    lines.join("\n")
+}
+
+/// Replaces 5etools tags such as `{@spell fireball|phb}` with their text, keeping
+/// bold and italic tags as Markdown.
+fn strip_tags(text: &str) -> String {
+    let mut plain = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{@") {
+        plain.push_str(&rest[..start]);
+        let mut depth = 0;
+        let mut end = None;
+        for (index, c) in rest[start..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        end = Some(start + index);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let end = match end {
+            Some(end) => end,
+            None => {
+                plain.push_str(&rest[start..]);
+                return plain;
+            }
+        };
+
+        let inner = strip_tags(&rest[start + 2..end]);
+        let (tag, content) = match inner.find(' ') {
+            Some(space) => (&inner[..space], &inner[space + 1..]),
+            None => (inner.as_str(), ""),
+        };
+        let parts: Vec<&str> = content.split('|').collect();
+        let display = match parts.get(2) {
+            Some(display) if !display.is_empty() => display,
+            _ => parts[0],
+        };
+        match tag {
+            "b" | "bold" => plain.push_str(&format!("**{}**", content)),
+            "i" | "italic" => plain.push_str(&format!("*{}*", content)),
+            "hit" if !display.starts_with('-') => plain.push_str(&format!("+{}", display)),
+            _ => plain.push_str(display),
+        }
+        rest = &rest[end + 1..];
+    }
+    plain.push_str(rest);
+
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_yaml::Value as Yaml;
+
+    const HOMEBREW: &str = r#"{
+  "spell": [
+    {
+      "name": "Frost Lance",
+      "level": 2,
+      "school": "V",
+      "time": [{ "number": 1, "unit": "bonus" }],
+      "components": { "v": true, "s": true, "m": { "text": "a shard of {@item ice|phb}", "cost": 100 } },
+      "duration": [{ "type": "timed", "duration": { "type": "minute", "amount": 1 }, "concentration": true }],
+      "meta": { "ritual": true },
+      "entries": [
+        "Make a {@spell ranged spell attack}, dealing {@damage 2d6 + 3} cold and {@damage 1d4} piercing damage on a {@hit 4} roll.",
+        "Damage also {@damage 3d8} later.",
+        { "type": "list", "items": ["{@b Slowed} for a round", "{@condition prone|phb|knocked over}"] },
+        {
+          "type": "entries",
+          "name": "Shards",
+          "entries": [
+            "The ice splinters.",
+            { "type": "table", "colLabels": ["d4", "Effect"], "rows": [["1", "Numb | cold"], ["2-4", "{@i Nothing}"]] }
+          ]
+        }
+      ],
+      "entriesHigherLevel": [
+        { "type": "entries", "name": "At Higher Levels", "entries": ["One more {@dice d6} per level."] }
+      ],
+      "damageInflict": ["cold", "piercing"],
+      "savingThrow": ["dexterity"],
+      "spellAttack": ["R"]
+    },
+    {
+      "name": "Broken Tag",
+      "level": 0,
+      "school": "X",
+      "time": [{ "number": 10, "unit": "minute" }],
+      "duration": [
+        { "type": "instant" },
+        { "type": "permanent", "ends": ["dispel"] }
+      ],
+      "entries": ["Deals {@damage 1d10} fire damage, then {@damage 2d4 on and on"],
+      "damageInflict": ["fire"]
+    }
+  ]
+}"#;
+
+    fn yaml<T: serde::Serialize>(value: &T) -> Yaml {
+        serde_yaml::to_value(value).unwrap()
+    }
+
+    fn expected(text: &str) -> Yaml {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn converts_every_spell_field() {
+        let spells = import_spells(HOMEBREW).unwrap();
+        let spell = &spells[0];
+
+        assert_eq!(spell.name, "Frost Lance");
+        assert_eq!(spell.school, "Evocation");
+        assert_eq!(
+            yaml(&spell.spell_level),
+            expected("{type: Level, number: 2}")
+        );
+        assert_eq!(
+            yaml(&spell.casting_time),
+            expected("{type: bonus-action, count: 1}")
+        );
+        assert_eq!(
+            spell.components.as_deref(),
+            Some("V, S, M (a shard of ice)")
+        );
+        assert_eq!(
+            spell.duration.as_deref(),
+            Some("Concentration, up to 1 minute")
+        );
+        assert!(spell.is_ritual);
+        assert_eq!(
+            yaml(&spell.actions),
+            expected(
+                r#"
+attacks:
+  - range: ranged
+    save: { type: dc }
+saves:
+  - is-magic: true
+    stat: { type: ability-score, ability: dex }
+    save: { type: dc }
+damages:
+  - damage:
+      - modifier: { damage-mod: none }
+        damage-type: cold
+        dice: [{ dice-type: d6, count: 2 }]
+        bonus: 3
+      - modifier: { damage-mod: none }
+        damage-type: piercing
+        dice: [{ dice-type: d4, count: 1 }]
+effects: []
+"#
+            )
+        );
+    }
+
+    #[test]
+    fn entries_become_markdown() {
+        let spells = import_spells(HOMEBREW).unwrap();
+        let blocks: Vec<&str> = spells[0].description.split("\n\n").collect();
+
+        assert_eq!(
+            blocks,
+            vec![
+                "Make a ranged spell attack, dealing 2d6 + 3 cold and 1d4 piercing damage on a +4 roll.",
+                "Damage also 3d8 later.",
+                "- **Slowed** for a round\n- knocked over",
+                "**Shards.** The ice splinters.",
+                "| d4 | Effect |\n| --- | --- |\n| 1 | Numb \\| cold |\n| 2-4 | *Nothing* |",
+                "**At Higher Levels.** One more d6 per level.",
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_damage_found_before_an_unterminated_tag() {
+        let spells = import_spells(HOMEBREW).unwrap();
+        let spell = &spells[1];
+
+        assert_eq!(
+            yaml(&spell.actions.damages),
+            expected(
+                r#"
+- damage:
+    - modifier: { damage-mod: none }
+      damage-type: fire
+      dice: [{ dice-type: d10, count: 1 }]
+"#
+            )
+        );
+        assert_eq!(yaml(&spell.spell_level), expected("{type: Cantrip}"));
+        assert_eq!(spell.school, "X");
+        assert_eq!(
+            yaml(&spell.casting_time),
+            expected("{type: minutes, count: 10}")
+        );
+        assert_eq!(
+            spell.duration.as_deref(),
+            Some("Instantaneous or Until dispelled")
+        );
+        assert_eq!(spell.components, None);
+    }
+
+    #[test]
+    fn damage_keeps_its_flat_bonus() {
+        let (dice, bonus) = damage_dice("2d6 + 1d4 + 3");
+        assert_eq!(
+            yaml(&dice),
+            expected("[{dice-type: d6, count: 2}, {dice-type: d4, count: 1}]")
+        );
+        assert_eq!(bonus, Some(3));
+        assert_eq!(damage_dice("1d8 - 1").1, Some(-1));
+        assert_eq!(damage_dice("1d8").1, None);
+    }
+
+    #[test]
+    fn unknown_casting_times_fail_the_import() {
+        let homebrew = r#"{"spell": [{"name": "Long Rite", "level": 1, "school": "A", "time": [{"number": 1, "unit": "round"}]}]}"#;
+        let error = import_spells(homebrew).unwrap_err().to_string();
+        assert!(error.contains("Long Rite"), "{}", error);
+    }
+
+    #[test]
+    fn tags_are_replaced_by_their_text() {
+        assert_eq!(strip_tags("{@spell fireball|phb}"), "fireball");
+        assert_eq!(strip_tags("{@creature goblin|mm|a goblin}"), "a goblin");
+        assert_eq!(
+            strip_tags("{@b bold {@i and italic}}"),
+            "**bold *and italic***"
+        );
+        assert_eq!(strip_tags("{@hit 5} or {@hit -1}"), "+5 or -1");
+        assert_eq!(strip_tags("left {@b open"), "left {@b open");
+    }
+}
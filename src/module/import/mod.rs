@@ -0,0 +1,100 @@
+use crate::module::inputs::*;
+
+mod fivetools;
+mod open5e;
+mod tables;
+
+pub use tables::import_table;
+
+const DAMAGE_TYPES: &[&str] = &[
+    "acid",
+    "bludgeoning",
+    "cold",
+    "fire",
+    "force",
+    "lightning",
+    "necrotic",
+    "piercing",
+    "poison",
+    "psychic",
+    "radiant",
+    "slashing",
+    "thunder",
+];
+
+/// Converts the spells of a homebrew JSON file into spell definitions.
+pub fn import_spells(format: &str, contents: &str) -> Result<Vec<SpellDefinition>, anyhow::Error> {
+    match format {
+        "5etools" => fivetools::import_spells(contents),
+        "open5e" => open5e::import_spells(contents),
+        other => anyhow::bail!(
+            "Unable to import spells from {}, use 5etools or open5e",
+            other
+        ),
+    }
+}
+
+fn spell_level(level: u8) -> SpellLevel {
+    match level {
+        0 => SpellLevel::Cantrip,
+        number => SpellLevel::Level { number },
+    }
+}
+
+/// Picks the casting time out of a count and unit such as `10 minute`.
+fn casting_time(spell: &str, count: u8, unit: &str) -> Result<SpellCastDuration, anyhow::Error> {
+    let unit = unit.trim().to_lowercase();
+    let casting_time = match unit.as_str() {
+        "action" | "actions" => SpellCastDuration::Action { count },
+        "bonus" | "bonus action" | "bonus actions" => SpellCastDuration::BonusAction { count },
+        "reaction" | "reactions" => SpellCastDuration::Reaction,
+        "minute" | "minutes" => SpellCastDuration::Minutes { count },
+        "hour" | "hours" => SpellCastDuration::Hours { count },
+        other => anyhow::bail!(
+            "Spell {} takes {} {} to cast, which isn't a casting time a module can hold",
+            spell,
+            count,
+            other
+        ),
+    };
+    Ok(casting_time)
+}
+
+fn damage_type(word: &str) -> Option<&'static str> {
+    let word = word
+        .trim_matches(|c: char| !c.is_ascii_alphabetic())
+        .to_lowercase();
+    DAMAGE_TYPES
+        .iter()
+        .copied()
+        .find(|damage_type| *damage_type == word)
+}
+
+/// The dice and flat bonus of a damage roll such as `2d6 + 4`.
+fn damage_dice(text: &str) -> (Vec<Dice>, Option<i32>) {
+    let mut dice = Vec::new();
+    let mut bonus = 0;
+    for term in text.replace('-', "+-").split('+') {
+        let term: String = term.split_whitespace().collect();
+        match term.parse::<i32>() {
+            Ok(number) => bonus += number,
+            Err(_) => dice.extend(Dice::parse_expression(&term).into_iter().flatten()),
+        }
+    }
+    (dice, Some(bonus).filter(|bonus| *bonus != 0))
+}
+
+fn spell_saves<'a, I>(abilities: I) -> Vec<SpellSave>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    abilities
+        .into_iter()
+        .filter_map(AbilityScore::from_long_name)
+        .map(|ability| SpellSave {
+            is_magic: true,
+            stat: SpellStat::AbilityScore { ability },
+            save: ToSave::DC,
+        })
+        .collect()
+}
@@ -0,0 +1,343 @@
+use crate::module::inputs::*;
+use super::{casting_time, damage_dice, damage_type, spell_level, spell_saves};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Open5e answers with a page of results, but a plain list of spells works too.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Open5eFile {
+    Page { results: Vec<Open5eSpell> },
+    List(Vec<Open5eSpell>),
+}
+
+#[derive(Deserialize)]
+struct Open5eSpell {
+    name: String,
+    #[serde(default)]
+    desc: String,
+    #[serde(default)]
+    higher_level: String,
+    #[serde(default)]
+    components: String,
+    #[serde(default)]
+    material: String,
+    #[serde(default)]
+    ritual: Value,
+    #[serde(default)]
+    duration: String,
+    #[serde(default)]
+    concentration: Value,
+    #[serde(default)]
+    casting_time: String,
+    level_int: Option<u8>,
+    #[serde(default)]
+    level: String,
+    #[serde(default)]
+    school: String,
+}
+
+pub fn import_spells(contents: &str) -> Result<Vec<SpellDefinition>, anyhow::Error> {
+    let spells = match serde_json::from_str(contents)? {
+        Open5eFile::Page { results } => results,
+        Open5eFile::List(spells) => spells,
+    };
+    spells.iter().map(Open5eSpell::to_spell).collect()
+}
+
+impl Open5eSpell {
+    fn to_spell(&self) -> Result<SpellDefinition, anyhow::Error> {
+        let mut description = self.desc.trim().to_owned();
+        if !self.higher_level.trim().is_empty() {
+            description.push_str(&format!(
+                "\n\n**At Higher Levels.** {}",
+                self.higher_level.trim()
+            ));
+        }
+        // the level is "Cantrip" or "3rd-level" when there's no level_int
+        let level = self.level_int.unwrap_or_else(|| {
+            self.level
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect::<String>()
+                .parse()
+                .unwrap_or(0)
+        });
+
+        Ok(SpellDefinition {
+            id: RecordId::default(),
+            name: self.name.clone(),
+            short_description: None,
+            duration: self.duration_text(),
+            components: self.components_text(),
+            casting_time: self.casting_time()?,
+            school: capitalize(&self.school),
+            spell_level: spell_level(level),
+            needs_preperation: false,
+            is_ritual: is_yes(&self.ritual),
+            group: "Spells".to_owned(),
+            actions: text_actions(&self.desc),
+            description,
+        })
+    }
+
+    fn casting_time(&self) -> Result<SpellCastDuration, anyhow::Error> {
+        if let Some(casting_time) = SpellCastDuration::parse(&self.casting_time) {
+            return Ok(casting_time);
+        }
+        let text = self.casting_time.trim();
+        let (count, unit) = text.split_at(text.find(' ').unwrap_or(0));
+        casting_time(&self.name, count.parse().unwrap_or(1), unit)
+    }
+
+    fn components_text(&self) -> Option<String> {
+        let components = self.components.trim();
+        if components.is_empty() {
+            return None;
+        }
+        let material = self.material.trim().trim_end_matches('.');
+        if material.is_empty() || components.contains('(') {
+            Some(components.to_owned())
+        } else {
+            Some(format!("{} ({})", components, material))
+        }
+    }
+
+    fn duration_text(&self) -> Option<String> {
+        let duration = self.duration.trim();
+        if duration.is_empty() {
+            return None;
+        }
+        if is_yes(&self.concentration) && !duration.to_lowercase().starts_with("concentration") {
+            let mut duration = duration.to_owned();
+            if let Some(first) = duration.get_mut(..1) {
+                first.make_ascii_lowercase();
+            }
+            Some(format!("Concentration, {}", duration))
+        } else {
+            Some(duration.to_owned())
+        }
+    }
+}
+
+/// Open5e has no structured actions, so they're read from the description: attack
+/// rolls, saving throws and the damage of the first paragraph that deals any.
+fn text_actions(description: &str) -> SpellActions {
+    let lower = description.to_lowercase();
+    let mut attacks = Vec::new();
+    if lower.contains("melee spell attack") {
+        attacks.push(SpellRange {
+            range: AttackRange::Melee,
+            save: ToSave::DC,
+        });
+    }
+    if lower.contains("ranged spell attack") {
+        attacks.push(SpellRange {
+            range: AttackRange::Ranged,
+            save: ToSave::DC,
+        });
+    }
+
+    let mut abilities: Vec<(usize, &str)> = [
+        "strength",
+        "dexterity",
+        "constitution",
+        "intelligence",
+        "wisdom",
+        "charisma",
+    ]
+    .iter()
+    .filter_map(|ability| {
+        lower
+            .find(&format!("{} saving throw", ability))
+            .map(|index| (index, *ability))
+    })
+    .collect();
+    abilities.sort_unstable();
+
+    let damages = description
+        .split("\n\n")
+        .map(paragraph_damage)
+        .find(|damage| !damage.is_empty())
+        .map(|damage| ActionDamage { damage })
+        .into_iter()
+        .collect();
+
+    SpellActions {
+        attacks,
+        saves: spell_saves(abilities.into_iter().map(|(_, ability)| ability)),
+        damages,
+        effects: Vec::new(),
+    }
+}
+
+/// Finds rolls like "3d6 fire damage" in a paragraph.
+fn paragraph_damage(paragraph: &str) -> Vec<SpellDamage> {
+    let words: Vec<&str> = paragraph.split_whitespace().collect();
+    words
+        .windows(3)
+        .filter(|window| window[2].to_lowercase().starts_with("damage"))
+        .filter_map(|window| {
+            let (dice, bonus) =
+                damage_dice(window[0].trim_matches(|c: char| !c.is_ascii_alphanumeric()));
+            let damage_type = damage_type(window[1])?;
+            if dice.is_empty() {
+                return None;
+            }
+            Some(SpellDamage {
+                modifier: DamageModifier::None,
+                damage_type: damage_type.to_owned(),
+                dice,
+                bonus,
+            })
+        })
+        .collect()
+}
+
+fn is_yes(value: &Value) -> bool {
+    match value {
+        Value::Bool(yes) => *yes,
+        Value::String(text) => text.eq_ignore_ascii_case("yes"),
+        _ => false,
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut text = text.trim().to_owned();
+    if let Some(first) = text.get_mut(..1) {
+        first.make_ascii_uppercase();
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_yaml::Value as Yaml;
+
+    const PAGE: &str = r#"{
+  "count": 1,
+  "results": [
+    {
+      "name": "Cinder Spray",
+      "desc": "Make a ranged spell attack. On a hit the target takes 3d6 fire damage.\n\nEach creature nearby makes a Dexterity saving throw or takes 1d4 cold damage.",
+      "higher_level": "One more d6 per slot level.",
+      "components": "V, S, M",
+      "material": "A pinch of sulfur.",
+      "ritual": "no",
+      "duration": "Up to 1 minute",
+      "concentration": "yes",
+      "casting_time": "1 bonus action",
+      "level_int": 1,
+      "level": "1st-level",
+      "school": "evocation"
+    }
+  ]
+}"#;
+
+    const LIST: &str = r#"[
+  {
+    "name": "Slow Ward",
+    "desc": "A ward that asks for a Wisdom saving throw, then a Strength saving throw.",
+    "components": "V, M (a silver bell)",
+    "material": "a silver bell",
+    "ritual": true,
+    "duration": "8 hours",
+    "casting_time": "10 minutes",
+    "level": "3rd-level",
+    "school": "abjuration"
+  }
+]"#;
+
+    fn yaml<T: serde::Serialize>(value: &T) -> Yaml {
+        serde_yaml::to_value(value).unwrap()
+    }
+
+    fn expected(text: &str) -> Yaml {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn converts_a_page_of_spells() {
+        let spells = import_spells(PAGE).unwrap();
+        let spell = &spells[0];
+
+        assert_eq!(spell.name, "Cinder Spray");
+        assert_eq!(spell.school, "Evocation");
+        assert_eq!(
+            yaml(&spell.spell_level),
+            expected("{type: Level, number: 1}")
+        );
+        assert_eq!(
+            yaml(&spell.casting_time),
+            expected("{type: bonus-action, count: 1}")
+        );
+        assert_eq!(
+            spell.components.as_deref(),
+            Some("V, S, M (A pinch of sulfur)")
+        );
+        assert_eq!(
+            spell.duration.as_deref(),
+            Some("Concentration, up to 1 minute")
+        );
+        assert!(!spell.is_ritual);
+        assert!(spell
+            .description
+            .ends_with("\n\n**At Higher Levels.** One more d6 per slot level."));
+        // only the first paragraph with damage counts
+        assert_eq!(
+            yaml(&spell.actions),
+            expected(
+                r#"
+attacks:
+  - range: ranged
+    save: { type: dc }
+saves:
+  - is-magic: true
+    stat: { type: ability-score, ability: dex }
+    save: { type: dc }
+damages:
+  - damage:
+      - modifier: { damage-mod: none }
+        damage-type: fire
+        dice: [{ dice-type: d6, count: 3 }]
+effects: []
+"#
+            )
+        );
+    }
+
+    #[test]
+    fn converts_a_list_of_spells() {
+        let spells = import_spells(LIST).unwrap();
+        let spell = &spells[0];
+
+        assert_eq!(
+            yaml(&spell.spell_level),
+            expected("{type: Level, number: 3}")
+        );
+        assert_eq!(
+            yaml(&spell.casting_time),
+            expected("{type: minutes, count: 10}")
+        );
+        assert_eq!(spell.components.as_deref(), Some("V, M (a silver bell)"));
+        assert_eq!(spell.duration.as_deref(), Some("8 hours"));
+        assert!(spell.is_ritual);
+        // saves in the order the description asks for them
+        assert_eq!(
+            yaml(&spell.actions.saves),
+            expected(
+                r#"
+- is-magic: true
+  stat: { type: ability-score, ability: wis }
+  save: { type: dc }
+- is-magic: true
+  stat: { type: ability-score, ability: str }
+  save: { type: dc }
+"#
+            )
+        );
+        assert!(spell.actions.attacks.is_empty());
+        assert!(spell.actions.damages.is_empty());
+    }
+}
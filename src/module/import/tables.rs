@@ -0,0 +1,304 @@
+use crate::module::inputs::*;
+use std::path::PathBuf;
+
+/// Converts a CSV file or a Markdown pipe table into a table definition. The first
+/// column holds the roll ranges and the other headers become column labels. Unless a
+/// name is given, the table is named after the heading above it or after the file.
+pub fn import_table(
+    format: &str,
+    contents: &str,
+    name: Option<&str>,
+    file_name: &str,
+) -> Result<TableDefinition, anyhow::Error> {
+    let (heading, rows) = match format {
+        "csv" => (None, csv_rows(contents)),
+        "markdown" => markdown_rows(contents),
+        other => anyhow::bail!(
+            "Unable to import a table from {}, use csv or markdown",
+            other
+        ),
+    };
+    let name = name
+        .map(str::to_owned)
+        .or(heading)
+        .unwrap_or_else(|| file_name.to_owned());
+
+    let mut rows = rows
+        .into_iter()
+        .filter(|row| row.iter().any(|cell| !cell.is_empty()));
+    let header = rows
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("Table {} has no rows", name))?;
+    if header.len() < 2 {
+        anyhow::bail!(
+            "Table {} needs a range column and at least one result column",
+            name
+        );
+    }
+    let dice = match header[0].to_lowercase().as_str() {
+        "d%" => Some("d100".to_owned()),
+        roll if Dice::parse_expression(roll).is_some() => Some(roll.to_owned()),
+        _ => None,
+    };
+    let columns: Vec<String> = header[1..].to_vec();
+
+    let mut ranges = Vec::new();
+    for (index, mut row) in rows.enumerate() {
+        if row.len() > header.len() {
+            anyhow::bail!(
+                "Table {} row {} has {} cells, but the header only has {}",
+                name,
+                index + 1,
+                row.len(),
+                header.len()
+            );
+        }
+        row.resize(header.len(), String::new());
+        let (from, until) = parse_range(&row[0], dice.as_deref()).ok_or_else(|| {
+            anyhow::anyhow!(
+                "Table {} row {} has an invalid range '{}'",
+                name,
+                index + 1,
+                row[0]
+            )
+        })?;
+
+        let mut results = row.split_off(1);
+        // a result that's only a link opens the linked record
+        let link = RecordReference::parse(&results[0]);
+        if let Some(reference) = &link {
+            results[0] = reference.name.clone();
+        }
+        let description = if results.len() == 1 {
+            results.remove(0)
+        } else {
+            String::new()
+        };
+
+        ranges.push(TableRange {
+            from,
+            until,
+            description,
+            results,
+            link,
+            line: 0,
+        });
+    }
+
+    Ok(TableDefinition {
+        id: RecordId::default(),
+        name,
+        description: String::new(),
+        formatted_text: None,
+        columns,
+        dice,
+        modifier: 0,
+        ranges,
+        path: PathBuf::new(),
+    })
+}
+
+/// Parses a range such as `01-05`, `7`, `-2--1` or `96-00`, where `00` is 100 on a d100.
+fn parse_range(text: &str, dice: Option<&str>) -> Option<(i32, i32)> {
+    let number = |text: &str| -> Option<i32> {
+        let text = text.trim();
+        match text {
+            "00" if dice == Some("d100") => Some(100),
+            _ => text.parse().ok(),
+        }
+    };
+    let text = text.trim().replace(|c| c == '–' || c == '—', "-");
+    // the first dash after a leading minus sign separates the range
+    match text.char_indices().skip(1).find(|(_, c)| *c == '-') {
+        Some((index, _)) => Some((number(&text[..index])?, number(&text[index + 1..])?)),
+        None => number(&text).map(|roll| (roll, roll)),
+    }
+}
+
+fn csv_rows(contents: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut cell).trim().to_owned()),
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut cell).trim().to_owned());
+                rows.push(std::mem::take(&mut row));
+            }
+            '\r' if !quoted => {}
+            c => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell.trim().to_owned());
+        rows.push(row);
+    }
+
+    rows
+}
+
+/// Reads the first pipe table of a Markdown file, along with the heading above it.
+fn markdown_rows(contents: &str) -> (Option<String>, Vec<Vec<String>>) {
+    let mut heading = None;
+    let mut rows = Vec::new();
+
+    for line in contents.lines().map(str::trim) {
+        if !line.starts_with('|') {
+            if !rows.is_empty() {
+                break;
+            }
+            if line.starts_with('#') {
+                heading = Some(line.trim_start_matches('#').trim().to_owned());
+            }
+            continue;
+        }
+
+        let line = line.trim_start_matches('|');
+        let line = line.strip_suffix('|').unwrap_or(line);
+        let mut cells = Vec::new();
+        let mut cell = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.peek() == Some(&'|') => {
+                    cell.push('|');
+                    chars.next();
+                }
+                '|' => cells.push(std::mem::take(&mut cell).trim().to_owned()),
+                c => cell.push(c),
+            }
+        }
+        cells.push(cell.trim().to_owned());
+
+        // the line below the header only lines up the columns
+        let is_separator = cells
+            .iter()
+            .all(|cell| !cell.is_empty() && cell.chars().all(|c| matches!(c, '-' | ':' | ' ')));
+        if !is_separator {
+            rows.push(cells);
+        }
+    }
+
+    (heading, rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ranges(table: &TableDefinition) -> Vec<(i32, i32)> {
+        table
+            .ranges
+            .iter()
+            .map(|range| (range.from, range.until))
+            .collect()
+    }
+
+    #[test]
+    fn reads_ranges() {
+        let d100 = Some("d100");
+        assert_eq!(parse_range("01-05", d100), Some((1, 5)));
+        assert_eq!(parse_range("1–3", None), Some((1, 3)));
+        assert_eq!(parse_range("4—6", None), Some((4, 6)));
+        assert_eq!(parse_range(" 7 ", None), Some((7, 7)));
+        assert_eq!(parse_range("96-00", d100), Some((96, 100)));
+        assert_eq!(parse_range("00", d100), Some((100, 100)));
+        assert_eq!(parse_range("00", Some("d10")), Some((0, 0)));
+        assert_eq!(parse_range("-2--1", None), Some((-2, -1)));
+        assert_eq!(parse_range("-3", None), Some((-3, -3)));
+        assert_eq!(parse_range("one", None), None);
+        assert_eq!(parse_range("1-", None), None);
+    }
+
+    #[test]
+    fn imports_a_csv_file() {
+        let table = import_table(
+            "csv",
+            "d6,Creature,Number\r\n1-2,Goblin,1d4\r\n3–5,\"Wolf, \"\"dire\"\"\",2\r\n\r\n6,[[npc:Ogre]]\r\n",
+            None,
+            "encounters",
+        )
+        .unwrap();
+
+        assert_eq!(table.name, "encounters");
+        assert_eq!(table.dice.as_deref(), Some("d6"));
+        assert_eq!(table.columns, vec!["Creature", "Number"]);
+        assert_eq!(ranges(&table), vec![(1, 2), (3, 5), (6, 6)]);
+        assert_eq!(table.ranges[1].results, vec!["Wolf, \"dire\"", "2"]);
+        // short rows are padded and a lone link opens the record
+        assert_eq!(table.ranges[2].results, vec!["Ogre", ""]);
+        let link = table.ranges[2].link.as_ref().unwrap();
+        assert_eq!(
+            (link.record_type.as_str(), link.name.as_str()),
+            ("npc", "Ogre")
+        );
+    }
+
+    #[test]
+    fn imports_a_markdown_table() {
+        let table = import_table(
+            "markdown",
+            "Intro text.\n\n## Trinkets\n\n| d% | Result |\n|:---:|---|\n| 01-50 | A bent coin \\| or two |\n| 51-00 | A **gold** ring |\n\n| 1 | Second table |\n",
+            None,
+            "loot",
+        )
+        .unwrap();
+
+        assert_eq!(table.name, "Trinkets");
+        assert_eq!(table.dice.as_deref(), Some("d100"));
+        assert_eq!(table.columns, vec!["Result"]);
+        assert_eq!(ranges(&table), vec![(1, 50), (51, 100)]);
+        assert_eq!(table.ranges[0].description, "A bent coin | or two");
+        assert!(table.ranges[0].results.is_empty());
+        assert_eq!(table.ranges[1].description, "A **gold** ring");
+    }
+
+    #[test]
+    fn names_come_from_the_argument_before_the_heading() {
+        let table = import_table(
+            "markdown",
+            "# Heading\n| Roll | Result |\n| --- | --- |\n| 1 | Rain |",
+            Some("Weather"),
+            "file",
+        )
+        .unwrap();
+        assert_eq!(table.name, "Weather");
+        // a header that isn't a dice expression leaves the dice out
+        assert_eq!(table.dice, None);
+    }
+
+    #[test]
+    fn rejects_broken_rows() {
+        let error = |format: &str, contents: &str| {
+            import_table(format, contents, None, "broken")
+                .unwrap_err()
+                .to_string()
+        };
+
+        assert_eq!(
+            error("csv", "d6,Result\n1-2,Rain,Hail\n"),
+            "Table broken row 1 has 3 cells, but the header only has 2"
+        );
+        assert_eq!(
+            error("markdown", "| d6 | Result |\n| --- | --- |\n| one | Rain |"),
+            "Table broken row 1 has an invalid range 'one'"
+        );
+        assert_eq!(
+            error("csv", "Result\n"),
+            "Table broken needs a range column and at least one result column"
+        );
+        assert_eq!(
+            error("json", "{}"),
+            "Unable to import a table from json, use csv or markdown"
+        );
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::sync::atomic::Ordering;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize, Deserialize)]
 
@@ -30,6 +30,30 @@ impl AbilityScore {
         }
         .to_owned()
     }
+
+    pub fn from_long_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "strength" => Some(AbilityScore::Strength),
+            "dexterity" => Some(AbilityScore::Dexterity),
+            "constitution" => Some(AbilityScore::Constitution),
+            "intelligence" => Some(AbilityScore::Intelligence),
+            "wisdom" => Some(AbilityScore::Wisdom),
+            "charisma" => Some(AbilityScore::Charisma),
+            _ => None,
+        }
+    }
+
+    pub fn to_short_name(&self) -> String {
+        match self {
+            AbilityScore::Strength => "Str",
+            AbilityScore::Dexterity => "Dex",
+            AbilityScore::Constitution => "Con",
+            AbilityScore::Intelligence => "Int",
+            AbilityScore::Wisdom => "Wis",
+            AbilityScore::Charisma => "Cha",
+        }
+        .to_owned()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,9 +63,34 @@ pub enum SpellCastDuration {
     Reaction,
     BonusAction { count: u8 },
     Action { count: u8 },
+    Minutes { count: u8 },
+    Hours { count: u8 },
     Forever
 }
 
+impl SpellCastDuration {
+    /// Parses a casting time such as `1 action`, `2 bonus actions`, `1 reaction` or `10 minutes`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim().to_lowercase();
+        match text.as_str() {
+            "1 reaction" | "reaction" => return Some(SpellCastDuration::Reaction),
+            "instant" => return Some(SpellCastDuration::Instant),
+            "forever" => return Some(SpellCastDuration::Forever),
+            _ => {}
+        }
+
+        let (count, unit) = text.split_at(text.find(' ')?);
+        let count = count.parse().ok()?;
+        match unit.trim().trim_end_matches('s') {
+            "bonus action" => Some(SpellCastDuration::BonusAction { count }),
+            "action" => Some(SpellCastDuration::Action { count }),
+            "minute" => Some(SpellCastDuration::Minutes { count }),
+            "hour" => Some(SpellCastDuration::Hours { count }),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum TimeUnit {
@@ -104,7 +153,7 @@ pub enum SpellStat {
 pub struct CustomSpellSave {
     pub stat: SpellStat,
     pub is_proficient: bool,
-    #[serde(skip, default = "Default::default")]
+    #[serde(default = "Default::default")]
     pub bonus: u8,
 }
 
@@ -132,12 +181,43 @@ pub struct Dice {
     pub count: u8,
 }
 
+impl Dice {
+    /// Parses a dice expression such as `2d6`, `d100` or `1d8+1d12`.
+    pub fn parse_expression(text: &str) -> Option<Vec<Self>> {
+        text.split('+')
+            .map(|term| {
+                let term = term.trim().to_lowercase();
+                let (count, sides) = term.split_at(term.find('d')?);
+                let count = if count.is_empty() {
+                    1
+                } else {
+                    count.parse().ok()?
+                };
+                let sides: u16 = sides[1..].parse().ok()?;
+                if count == 0 || sides == 0 {
+                    return None;
+                }
+                Some(Self {
+                    dice_type: format!("d{}", sides),
+                    count,
+                })
+            })
+            .collect()
+    }
+
+    pub fn sides(&self) -> u16 {
+        self.dice_type[1..].parse().unwrap_or_default()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct SpellDamage {
     pub modifier: DamageModifier,
     pub damage_type: String,
     pub dice: Vec<Dice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bonus: Option<i32>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -154,7 +234,7 @@ pub struct ActionDamage {
     pub damage: Vec<SpellDamage>
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct SpellActions {
     #[serde(default = "Default::default")]
@@ -170,12 +250,14 @@ pub struct SpellActions {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct SpellDefinition {
-    #[serde(skip, default = "Default::default")]
-    pub id: SpellId,
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
     pub name: String,
     pub short_description: Option<String>,
     pub description: String,
     pub duration: Option<String>,
+    #[serde(default = "Default::default", skip_serializing_if = "Option::is_none")]
+    pub components: Option<String>,
     pub casting_time: SpellCastDuration,
     pub school: String,
     pub spell_level: SpellLevel,
@@ -185,21 +267,35 @@ pub struct SpellDefinition {
     pub actions: SpellActions
 }
 
-#[derive(Debug)]
-pub struct SpellId {
-    value: u8
+/// Identifies a record within its node. Unless one is given in the YAML, the id is derived
+/// from the record name so it stays the same between builds.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RecordId {
+    value: Option<u32>,
 }
 
-impl SpellId {
-    pub fn get_id(&self) -> u8 {
-        self.value
+impl RecordId {
+    pub fn get_id(&self, name: &str) -> u32 {
+        self.value.unwrap_or_else(|| {
+            // FNV-1a, which unlike the std hasher is guaranteed to never change
+            let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+                (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+            });
+            hash % 99_999_999 + 1
+        })
     }
-}
 
-impl Default for SpellId {
-    fn default() -> Self {
-        Self {
-            value: super::SPELL_ID_COUNTER.fetch_add(1, Ordering::SeqCst)
+    pub fn is_derived(&self) -> bool {
+        self.value.is_none()
+    }
+
+    /// Keeps an id read from an existing module, unless the name would derive it anyway.
+    pub fn extracted(id: u32, name: &str) -> Self {
+        if Self::default().get_id(name) == id {
+            Self::default()
+        } else {
+            Self { value: Some(id) }
         }
     }
 }
@@ -208,30 +304,773 @@ impl Default for SpellId {
 pub enum ModuleCategory {
     #[serde(rename = "Source Book")]
     SourceBook,
+    #[serde(rename = "Tokens")]
+    Tokens,
+}
+
+impl Default for ModuleCategory {
+    fn default() -> Self {
+        ModuleCategory::SourceBook
+    }
+}
+
+impl ModuleCategory {
+    pub fn to_name(&self) -> String {
+        match self {
+            ModuleCategory::SourceBook => "Source Book",
+            ModuleCategory::Tokens => "Tokens",
+        }
+        .to_owned()
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct TableRange {
-    pub from: u8,
-    pub until: u8,
+    pub from: i32,
+    pub until: i32,
+    /// Shorthand for a table with a single result column.
+    #[serde(default = "Default::default", skip_serializing_if = "String::is_empty")]
     pub description: String,
+    /// One result per column, for tables that declare `columns`.
+    #[serde(default = "Default::default", skip_serializing_if = "Vec::is_empty")]
+    pub results: Vec<String>,
+    /// Record opened from the first result column.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub link: Option<RecordReference>,
+    /// Line of the table file the range starts on.
+    #[serde(skip)]
+    pub line: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct TableDefinition {
-    #[serde(skip, default = "create_table_id")]
-    pub id: u8,
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
     pub name: String,
     pub description: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub formatted_text: Option<String>,
+    #[serde(default = "Default::default", skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dice: Option<String>,
+    #[serde(default = "Default::default")]
+    pub modifier: i16,
     pub ranges: Vec<TableRange>,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NpcArmorClass {
+    pub value: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NpcHitPoints {
+    pub average: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dice: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NpcAbilityScores {
+    #[serde(rename = "str")]
+    pub strength: u8,
+    #[serde(rename = "dex")]
+    pub dexterity: u8,
+    #[serde(rename = "con")]
+    pub constitution: u8,
+    #[serde(rename = "int")]
+    pub intelligence: u8,
+    #[serde(rename = "wis")]
+    pub wisdom: u8,
+    #[serde(rename = "cha")]
+    pub charisma: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NpcSavingThrow {
+    pub ability: AbilityScore,
+    pub bonus: i8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NpcSkill {
+    pub skill: String,
+    pub bonus: i8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NpcPower {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct NpcDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    pub size: String,
+    pub creature_type: String,
+    pub alignment: String,
+    pub armor_class: NpcArmorClass,
+    pub hit_points: NpcHitPoints,
+    pub speed: String,
+    pub abilities: NpcAbilityScores,
+    #[serde(default = "Default::default")]
+    pub saving_throws: Vec<NpcSavingThrow>,
+    #[serde(default = "Default::default")]
+    pub skills: Vec<NpcSkill>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub senses: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub languages: Option<String>,
+    pub challenge_rating: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default = "Default::default")]
+    pub traits: Vec<NpcPower>,
+    #[serde(default = "Default::default")]
+    pub actions: Vec<NpcPower>,
+    #[serde(default = "Default::default")]
+    pub reactions: Vec<NpcPower>,
+    #[serde(default = "Default::default")]
+    pub legendary_actions: Vec<NpcPower>,
+    #[serde(default = "Default::default")]
+    pub lair_actions: Vec<NpcPower>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ArmorDexBonus {
+    Full,
+    Limited { max: u8 },
+    None,
+}
+
+impl Default for ArmorDexBonus {
+    fn default() -> Self {
+        ArmorDexBonus::Full
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ItemKind {
+    #[serde(rename_all = "kebab-case")]
+    Weapon {
+        category: String,
+        damage: Vec<Dice>,
+        damage_type: String,
+        #[serde(default = "Default::default")]
+        properties: Vec<String>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    Armor {
+        category: String,
+        armor_class: u8,
+        #[serde(default = "Default::default")]
+        dex_bonus: ArmorDexBonus,
+        #[serde(default = "Default::default")]
+        stealth_disadvantage: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        strength: Option<u8>,
+    },
+    AdventuringGear {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        category: Option<String>,
+    },
+    Tool {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        category: Option<String>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    Other {
+        item_type: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        category: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ItemDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    pub kind: ItemKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ItemRarity {
+    Common,
+    Uncommon,
+    Rare,
+    VeryRare,
+    Legendary,
+    Artifact,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ItemAttunement {
+    None,
+    Required,
+    RequiredBy { by: String },
+}
+
+impl Default for ItemAttunement {
+    fn default() -> Self {
+        ItemAttunement::None
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MagicItemPower {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default = "Default::default")]
+    pub actions: SpellActions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct MagicItemDefinition {
+    #[serde(flatten)]
+    pub item: ItemDefinition,
+    pub rarity: ItemRarity,
+    #[serde(default = "Default::default")]
+    pub attunement: ItemAttunement,
+    /// What players see the item as until it's identified, the item type by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unidentified_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub charges: Option<u8>,
+    #[serde(default = "Default::default")]
+    pub powers: Vec<MagicItemPower>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FeatDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prerequisite: Option<String>,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BackgroundFeature {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BackgroundDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default = "Default::default")]
+    pub skill_proficiencies: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub tool_proficiencies: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub languages: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub equipment: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub features: Vec<BackgroundFeature>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AbilityScoreIncrease {
+    pub ability: AbilityScore,
+    pub increase: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RaceTrait {
+    pub name: String,
+    pub description: String,
+    #[serde(default = "Default::default")]
+    pub actions: SpellActions,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SubraceDefinition {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default = "Default::default")]
+    pub ability_score_increases: Vec<AbilityScoreIncrease>,
+    #[serde(default = "Default::default")]
+    pub traits: Vec<RaceTrait>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RaceDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default = "Default::default")]
+    pub ability_score_increases: Vec<AbilityScoreIncrease>,
+    pub size: String,
+    pub speed: u16,
+    #[serde(default = "Default::default")]
+    pub traits: Vec<RaceTrait>,
+    #[serde(default = "Default::default")]
+    pub subraces: Vec<SubraceDefinition>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpellcastingProgression {
+    None,
+    Full,
+    Half,
+    Third,
+    Pact,
+}
+
+impl Default for SpellcastingProgression {
+    fn default() -> Self {
+        SpellcastingProgression::None
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClassFeature {
+    pub level: u8,
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClassProficiencies {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub armor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weapons: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<String>,
+    #[serde(default = "Default::default")]
+    pub saving_throws: Vec<AbilityScore>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skills: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SubclassDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+    pub level: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default = "Default::default")]
+    pub spellcasting: SpellcastingProgression,
+    #[serde(default = "Default::default")]
+    pub features: Vec<ClassFeature>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ClassDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub hit_die: String,
+    #[serde(default = "Default::default")]
+    pub proficiencies: ClassProficiencies,
+    #[serde(default = "Default::default")]
+    pub spellcasting: SpellcastingProgression,
+    #[serde(default = "Default::default")]
+    pub features: Vec<ClassFeature>,
+    #[serde(default = "Default::default")]
+    pub subclasses: Vec<SubclassDefinition>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct StoryCategory {
+    pub category: String,
+    #[serde(default = "Default::default")]
+    pub files: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct StoryDefinition {
+    pub id: RecordId,
+    pub name: String,
+    pub category: String,
+    pub text: String,
+}
+
+impl StoryDefinition {
+    /// Builds a story entry from a Markdown file. A leading `# Heading` becomes
+    /// the entry name, otherwise the file name is used.
+    pub fn from_markdown(category: &str, file_name: &str, contents: &str) -> Self {
+        let trimmed = contents.trim_start();
+        let (name, text) = match trimmed.strip_prefix("# ") {
+            Some(rest) => {
+                let (heading, body) = rest.split_at(rest.find('\n').unwrap_or(rest.len()));
+                (heading.trim().to_owned(), body.to_owned())
+            }
+            None => (file_name.to_owned(), contents.to_owned()),
+        };
+
+        Self {
+            id: RecordId::default(),
+            name,
+            category: category.to_owned(),
+            text,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ReferenceManualDefinition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub chapters: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RecordReference {
+    #[serde(rename = "type")]
+    pub record_type: String,
+    pub name: String,
+}
+
+impl RecordReference {
+    /// Parses a `[[type:Record Name]]` link, the whole input must be the link.
+    pub fn parse(text: &str) -> Option<Self> {
+        let inner = text.trim().strip_prefix("[[")?.strip_suffix("]]")?;
+        let (record_type, name) = inner.split_at(inner.find(':')?);
+
+        Some(Self {
+            record_type: record_type.trim().to_lowercase(),
+            name: name[1..].trim().to_owned(),
+        })
+    }
+
+    /// Replaces every `[[type:Record Name]]` link in the text with the record name,
+    /// returning the rewritten text and the links in the order they appeared.
+    pub fn extract(text: &str) -> (String, Vec<Self>) {
+        Self::replace(text, |_, reference| reference.name.clone())
+    }
+
+    /// Replaces every link in the text with what the closure makes of it and the
+    /// link's position, returning the rewritten text and the links.
+    pub fn replace<F>(text: &str, mut replacement: F) -> (String, Vec<Self>)
+    where
+        F: FnMut(usize, &Self) -> String,
+    {
+        let mut plain = String::new();
+        let mut references = Vec::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("[[") {
+            let end = match rest[start..].find("]]") {
+                Some(end) => start + end + 2,
+                None => break,
+            };
+            match Self::parse(&rest[start..end]) {
+                Some(reference) => {
+                    plain.push_str(&rest[..start]);
+                    plain.push_str(&replacement(references.len(), &reference));
+                    references.push(reference);
+                }
+                None => plain.push_str(&rest[..end]),
+            }
+            rest = &rest[end..];
+        }
+        plain.push_str(rest);
+
+        (plain, references)
+    }
+}
+
+#[derive(Debug)]
+pub enum ReferenceBlock {
+    Text(String),
+    Image { path: PathBuf, caption: String },
+    Links(Vec<RecordReference>),
+}
+
+#[derive(Debug)]
+pub struct ReferencePage {
+    pub name: String,
+    pub blocks: Vec<ReferenceBlock>,
+}
+
+#[derive(Debug)]
+pub struct ReferenceSubchapter {
+    pub name: String,
+    pub pages: Vec<ReferencePage>,
+}
+
+#[derive(Debug)]
+pub struct ReferenceChapter {
+    pub name: String,
+    pub subchapters: Vec<ReferenceSubchapter>,
+}
+
+impl ReferenceChapter {
+    /// Splits a Markdown chapter into subchapters (`## Heading`) and pages
+    /// (`### Heading`). Lines holding only an image or only `[[type:Name]]`
+    /// links become their own blocks, everything else is text.
+    pub fn from_markdown(path: &Path, contents: &str) -> Self {
+        let mut chapter = ReferenceChapter {
+            name: path.file_stem().unwrap().to_string_lossy().to_string(),
+            subchapters: Vec::new(),
+        };
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut named = false;
+        let mut text = String::new();
+
+        for line in contents.lines() {
+            let trimmed = line.trim();
+            if let Some(name) = trimmed.strip_prefix("# ").filter(|_| !named) {
+                chapter.name = name.trim().to_owned();
+                named = true;
+            } else if let Some(name) = trimmed.strip_prefix("## ") {
+                chapter.flush_text(&mut text);
+                chapter.subchapters.push(ReferenceSubchapter {
+                    name: name.trim().to_owned(),
+                    pages: Vec::new(),
+                });
+            } else if let Some(name) = trimmed.strip_prefix("### ") {
+                chapter.flush_text(&mut text);
+                chapter.current_subchapter().pages.push(ReferencePage {
+                    name: name.trim().to_owned(),
+                    blocks: Vec::new(),
+                });
+            } else if let Some((caption, image)) = parse_image(trimmed) {
+                chapter.flush_text(&mut text);
+                chapter.push_block(ReferenceBlock::Image {
+                    path: base_dir.join(image),
+                    caption: caption.to_owned(),
+                });
+            } else if let Some(link) = RecordReference::parse(trimmed) {
+                chapter.flush_text(&mut text);
+                let page = chapter.current_page();
+                match page.blocks.last_mut() {
+                    Some(ReferenceBlock::Links(links)) => links.push(link),
+                    _ => page.blocks.push(ReferenceBlock::Links(vec![link])),
+                }
+            } else {
+                text.push_str(line);
+                text.push('\n');
+            }
+        }
+        chapter.flush_text(&mut text);
+
+        chapter
+    }
+
+    fn current_subchapter(&mut self) -> &mut ReferenceSubchapter {
+        if self.subchapters.is_empty() {
+            self.subchapters.push(ReferenceSubchapter {
+                name: self.name.clone(),
+                pages: Vec::new(),
+            });
+        }
+        self.subchapters.last_mut().unwrap()
+    }
+
+    fn current_page(&mut self) -> &mut ReferencePage {
+        let subchapter = self.current_subchapter();
+        if subchapter.pages.is_empty() {
+            subchapter.pages.push(ReferencePage {
+                name: subchapter.name.clone(),
+                blocks: Vec::new(),
+            });
+        }
+        subchapter.pages.last_mut().unwrap()
+    }
+
+    fn push_block(&mut self, block: ReferenceBlock) {
+        self.current_page().blocks.push(block);
+    }
+
+    fn flush_text(&mut self, text: &mut String) {
+        if !text.trim().is_empty() {
+            self.push_block(ReferenceBlock::Text(text.clone()));
+        }
+        text.clear();
+    }
 }
 
-fn create_table_id() -> u8 {
-    super::TABLE_ID_COUNTER.fetch_add(1, Ordering::SeqCst)
+fn parse_image(line: &str) -> Option<(&str, &str)> {
+    let inner = line.strip_prefix("![")?.strip_suffix(')')?;
+    let split = inner.find("](")?;
+    Some((&inner[..split], &inner[split + 2..]))
+}
+
+#[derive(Debug)]
+pub struct ReferenceManual {
+    pub name: String,
+    pub chapters: Vec<ReferenceChapter>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ImageGrid {
+    pub size: u32,
+    #[serde(default = "Default::default")]
+    pub offset_x: i32,
+    #[serde(default = "Default::default")]
+    pub offset_y: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ImageDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub grid: Option<ImageGrid>,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TokenPlacement {
+    pub x: i32,
+    pub y: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EncounterNpc {
+    pub name: String,
+    pub count: u8,
+    #[serde(default = "Default::default")]
+    pub placements: Vec<TokenPlacement>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EncounterDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub map: Option<String>,
+    pub npcs: Vec<EncounterNpc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ParcelCoins {
+    #[serde(default = "Default::default")]
+    pub pp: u32,
+    #[serde(default = "Default::default")]
+    pub gp: u32,
+    #[serde(default = "Default::default")]
+    pub ep: u32,
+    #[serde(default = "Default::default")]
+    pub sp: u32,
+    #[serde(default = "Default::default")]
+    pub cp: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ParcelItem {
+    /// An item defined in this module, linked by name.
+    Reference {
+        item: String,
+        #[serde(default = "default_parcel_count")]
+        count: u16,
+    },
+    /// A free-form item that only carries a name.
+    Named {
+        name: String,
+        #[serde(default = "default_parcel_count")]
+        count: u16,
+    },
+}
+
+fn default_parcel_count() -> u16 {
+    1
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ParcelDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    #[serde(default = "Default::default")]
+    pub coins: ParcelCoins,
+    #[serde(default = "Default::default")]
+    pub items: Vec<ParcelItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct QuestDefinition {
+    #[serde(default = "Default::default", skip_serializing_if = "RecordId::is_derived")]
+    pub id: RecordId,
+    pub name: String,
+    #[serde(default = "Default::default")]
+    pub xp: u32,
+    pub description: String,
+}
+
+#[derive(Debug)]
+pub struct TokenFile {
+    pub path: PathBuf,
+    pub archive_path: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -241,13 +1080,68 @@ pub enum RuleSet {
     FifthEdition,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct ModuleDefinition {
     pub name: String,
     pub spell_files: Vec<String>,
     pub table_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub npc_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub item_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub magic_item_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub feat_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub background_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub race_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub class_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub subclass_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub encounter_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub parcel_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub quest_files: Vec<String>,
+    #[serde(default = "Default::default")]
+    pub story_files: Vec<StoryCategory>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reference_manual: Option<ReferenceManualDefinition>,
+    #[serde(default = "Default::default")]
+    pub images: Vec<ImageDefinition>,
+    #[serde(default = "Default::default")]
+    pub tokens: Vec<String>,
     pub source: String,
     pub category: ModuleCategory,
     pub author: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_ids_derived_from_names_never_change() {
+        // modules already out there link to these ids
+        assert_eq!(RecordId::default().get_id("Fireball"), 81_882_345);
+        assert_eq!(RecordId::default().get_id("Searing Frost"), 29_279_863);
+        assert_eq!(RecordId::default().get_id("Ring"), 5_354_400);
+    }
+
+    #[test]
+    fn set_record_ids_win_over_names() {
+        let id: RecordId = serde_yaml::from_str("42").unwrap();
+        assert_eq!(id.get_id("Fireball"), 42);
+        assert!(!id.is_derived());
+
+        assert!(RecordId::extracted(81_882_345, "Fireball").is_derived());
+        assert_eq!(RecordId::extracted(7, "Fireball").get_id("Fireball"), 7);
+    }
 }
@@ -1,14 +1,9 @@
 mod cli;
+mod extract;
+mod import;
 mod inputs;
 mod output;
-
-use lazy_static::lazy_static;
+mod roundtrip;
+mod validate;
 
 pub use cli::ModuleSubCommand;
-
-use std::sync::atomic::AtomicU8;
-
-lazy_static! {
-    static ref SPELL_ID_COUNTER: AtomicU8 = AtomicU8::new(1);
-    static ref TABLE_ID_COUNTER: AtomicU8 = AtomicU8::new(1);
-}
@@ -0,0 +1,82 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::*;
+
+impl BackgroundDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            if !self.equipment.is_empty() {
+                builder.write_string(
+                    "equipment",
+                    vec![XmlAttribute::string()],
+                    self.equipment.join(", "),
+                )?;
+            }
+            self.features(links, builder)?;
+            if !self.languages.is_empty() {
+                builder.write_string(
+                    "languages",
+                    vec![XmlAttribute::string()],
+                    self.languages.join(", "),
+                )?;
+            }
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            if !self.skill_proficiencies.is_empty() {
+                builder.write_string(
+                    "skill",
+                    vec![XmlAttribute::string()],
+                    self.skill_proficiencies.join(", "),
+                )?;
+            }
+            builder.write_string("source", vec![XmlAttribute::string()], &module.name)?;
+            if let Some(description) = &self.description {
+                builder.write_raw(
+                    "text",
+                    vec![XmlAttribute::r#type("formattedtext")],
+                    links.formatted_text(description)?,
+                )?;
+            }
+            if !self.tool_proficiencies.is_empty() {
+                builder.write_string(
+                    "tool",
+                    vec![XmlAttribute::string()],
+                    self.tool_proficiencies.join(", "),
+                )?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn features(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        if self.features.is_empty() {
+            return Ok(());
+        }
+
+        w.child("features", vec![], |builder| {
+            let mut counter = 0;
+            for feature in &self.features {
+                counter += 1;
+                let id = format!("id-{:05}", counter);
+                builder.child(&id, vec![], |builder| {
+                    builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+                    builder.write_string("name", vec![XmlAttribute::string()], &feature.name)?;
+                    builder.write_raw(
+                        "text",
+                        vec![XmlAttribute::r#type("formattedtext")],
+                        links.formatted_text(&feature.description)?,
+                    )
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,263 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::*;
+
+impl ClassDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            self.abilities(links, builder)?;
+            self.spellcasting.process(builder)?;
+            self.features(links, builder)?;
+            builder.child("hitdice", vec![], |builder| {
+                builder.write_string("dice", vec![XmlAttribute::r#type("dice")], &self.hit_die)
+            })?;
+            self.hit_points(builder)?;
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            self.proficiencies(builder)?;
+            builder.write_string("source", vec![XmlAttribute::string()], &module.name)?;
+            if let Some(description) = &self.description {
+                builder.write_raw(
+                    "text",
+                    vec![XmlAttribute::r#type("formattedtext")],
+                    links.formatted_text(description)?,
+                )?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn abilities(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        if self.subclasses.is_empty() {
+            return Ok(());
+        }
+
+        w.child("abilities", vec![], |builder| {
+            let mut counter = 0;
+            for subclass in &self.subclasses {
+                counter += 1;
+                let id = format!("id-{:05}", counter);
+                builder.child(&id, vec![], |builder| {
+                    builder.write_string("level", vec![XmlAttribute::number()], subclass.level)?;
+                    builder.write_string("name", vec![XmlAttribute::string()], &subclass.name)?;
+                    subclass.spellcasting.process(builder)?;
+                    if let Some(description) = &subclass.description {
+                        builder.write_raw(
+                            "text",
+                            vec![XmlAttribute::r#type("formattedtext")],
+                            links.formatted_text(description)?,
+                        )?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })
+    }
+
+    fn features(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        w.child("features", vec![], |builder| {
+            let mut counter = 0;
+            for feature in &self.features {
+                counter += 1;
+                process_feature(counter, feature, None, links, builder)?;
+            }
+            for subclass in &self.subclasses {
+                for feature in &subclass.features {
+                    counter += 1;
+                    process_feature(counter, feature, Some(&subclass.name), links, builder)?;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn hit_points(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let die_size: u8 = self
+            .hit_die
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .trim_start_matches('d')
+            .parse()
+            .map_err(|_| {
+                anyhow::anyhow!("Invalid hit die {} for class {}", self.hit_die, self.name)
+            })?;
+        let class_name = self.name.to_lowercase();
+
+        w.child("hp", vec![], |builder| {
+            builder.child("hitdice", vec![], |builder| {
+                builder.write_string("name", vec![XmlAttribute::string()], "Hit Dice")?;
+                builder.write_string(
+                    "text",
+                    vec![XmlAttribute::string()],
+                    format!("1d{} per {} level", die_size, class_name),
+                )
+            })?;
+            builder.child("hitpointsat1stlevel", vec![], |builder| {
+                builder.write_string(
+                    "name",
+                    vec![XmlAttribute::string()],
+                    "Hit Points at 1st Level",
+                )?;
+                builder.write_string(
+                    "text",
+                    vec![XmlAttribute::string()],
+                    format!("{} + your Constitution modifier", die_size),
+                )
+            })?;
+            builder.child("hitpointsathigherlevels", vec![], |builder| {
+                builder.write_string(
+                    "name",
+                    vec![XmlAttribute::string()],
+                    "Hit Points at Higher Levels",
+                )?;
+                builder.write_string(
+                    "text",
+                    vec![XmlAttribute::string()],
+                    format!(
+                        "1d{} (or {}) + your Constitution modifier per {} level after 1st",
+                        die_size,
+                        die_size / 2 + 1,
+                        class_name
+                    ),
+                )
+            })
+        })
+    }
+
+    fn proficiencies(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let proficiencies = &self.proficiencies;
+        let saving_throws: Vec<String> = proficiencies
+            .saving_throws
+            .iter()
+            .map(|ability| {
+                let mut name = ability.to_long_name();
+                name[..1].make_ascii_uppercase();
+                name
+            })
+            .collect();
+
+        w.child("proficiencies", vec![], |builder| {
+            process_proficiency("armor", "Armor", &proficiencies.armor, builder)?;
+            process_proficiency(
+                "savingthrows",
+                "Saving Throws",
+                &Some(saving_throws.join(", ")).filter(|text| !text.is_empty()),
+                builder,
+            )?;
+            process_proficiency("skills", "Skills", &proficiencies.skills, builder)?;
+            process_proficiency("tools", "Tools", &proficiencies.tools, builder)?;
+            process_proficiency("weapons", "Weapons", &proficiencies.weapons, builder)
+        })
+    }
+}
+
+impl SubclassDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let class = self.class.as_ref().ok_or_else(|| {
+            anyhow::anyhow!("Subclass {} must name the class it belongs to", self.name)
+        })?;
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            builder.write_string("class", vec![XmlAttribute::string()], class)?;
+            self.spellcasting.process(builder)?;
+            builder.child("features", vec![], |builder| {
+                let mut counter = 0;
+                for feature in &self.features {
+                    counter += 1;
+                    process_feature(counter, feature, Some(&self.name), links, builder)?;
+                }
+                Ok(())
+            })?;
+            builder.write_string("level", vec![XmlAttribute::number()], self.level)?;
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            builder.write_string("source", vec![XmlAttribute::string()], &module.name)?;
+            if let Some(description) = &self.description {
+                builder.write_raw(
+                    "text",
+                    vec![XmlAttribute::r#type("formattedtext")],
+                    links.formatted_text(description)?,
+                )?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+}
+
+impl SpellcastingProgression {
+    fn process(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let multiplier = match self {
+            SpellcastingProgression::None => return Ok(()),
+            SpellcastingProgression::Pact => {
+                return w.write_string("casterpactmagic", vec![XmlAttribute::number()], 1)
+            }
+            SpellcastingProgression::Full => 1,
+            SpellcastingProgression::Half => 2,
+            SpellcastingProgression::Third => 3,
+        };
+
+        w.write_string(
+            "casterlevelinvmult",
+            vec![XmlAttribute::number()],
+            multiplier,
+        )
+    }
+}
+
+fn process_feature(
+    index: usize,
+    feature: &ClassFeature,
+    specialization: Option<&str>,
+    links: &RecordLinks,
+    w: &XmlBuilder,
+) -> Result<(), anyhow::Error> {
+    let id = format!("id-{:05}", index);
+    w.child(&id, vec![], |builder| {
+        builder.write_string("level", vec![XmlAttribute::number()], feature.level)?;
+        builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+        builder.write_string("name", vec![XmlAttribute::string()], &feature.name)?;
+        if let Some(specialization) = specialization {
+            builder.write_string(
+                "specialization",
+                vec![XmlAttribute::string()],
+                specialization,
+            )?;
+        }
+        builder.write_raw(
+            "text",
+            vec![XmlAttribute::r#type("formattedtext")],
+            links.formatted_text(&feature.description)?,
+        )
+    })
+}
+
+fn process_proficiency(
+    element_name: &str,
+    display_name: &str,
+    text: &Option<String>,
+    w: &XmlBuilder,
+) -> Result<(), anyhow::Error> {
+    if let Some(text) = text {
+        w.child(element_name, vec![], |builder| {
+            builder.write_string("name", vec![XmlAttribute::string()], display_name)?;
+            builder.write_string("text", vec![XmlAttribute::string()], text)
+        })?;
+    }
+
+    Ok(())
+}
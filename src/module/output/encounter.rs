@@ -0,0 +1,242 @@
+use crate::module::inputs::*;
+use super::links::{RecordLink, RecordLinks};
+use super::npc::challenge_xp;
+use super::*;
+use anyhow::Context;
+
+impl EncounterDefinition {
+    pub fn process(
+        &self,
+        npcs: &[NpcDefinition],
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+        let map = match &self.map {
+            Some(map) => {
+                let reference = RecordReference {
+                    record_type: "image".to_owned(),
+                    name: map.clone(),
+                };
+                let link = links
+                    .resolve(&reference)
+                    .with_context(|| format!("Encounter {} has an unknown map", self.name))?;
+                Some(link)
+            }
+            None => None,
+        };
+
+        let mut exp = 0;
+        for encounter_npc in &self.npcs {
+            let npc = npcs
+                .iter()
+                .find(|npc| npc.name == encounter_npc.name)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "Encounter {} references NPC {}, which is not in this module",
+                        self.name,
+                        encounter_npc.name
+                    )
+                })?;
+            if !encounter_npc.placements.is_empty() && map.is_none() {
+                anyhow::bail!(
+                    "Encounter {} places {} tokens without a map",
+                    self.name,
+                    encounter_npc.name
+                );
+            }
+            if encounter_npc.placements.len() > usize::from(encounter_npc.count) {
+                anyhow::bail!(
+                    "Encounter {} places {} {} tokens but only has {} of them",
+                    self.name,
+                    encounter_npc.placements.len(),
+                    encounter_npc.name,
+                    encounter_npc.count
+                );
+            }
+            let xp = challenge_xp(&npc.challenge_rating).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Encounter {} can't total its XP, NPC {} has an unknown challenge rating {}",
+                    self.name,
+                    npc.name,
+                    npc.challenge_rating
+                )
+            })?;
+            exp += xp * u32::from(encounter_npc.count);
+        }
+
+        w.child(&id, vec![], |builder| {
+            builder.write_string("exp", vec![XmlAttribute::number()], exp)?;
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            builder.child("npclist", vec![], |builder| {
+                let mut counter = 0;
+                for encounter_npc in &self.npcs {
+                    counter += 1;
+                    let reference = RecordReference {
+                        record_type: "npc".to_owned(),
+                        name: encounter_npc.name.clone(),
+                    };
+                    let link = links.resolve(&reference)?;
+                    let id = format!("id-{:05}", counter);
+                    builder.child(&id, vec![], |builder| {
+                        builder.write_string(
+                            "count",
+                            vec![XmlAttribute::number()],
+                            encounter_npc.count,
+                        )?;
+                        builder.child(
+                            "link",
+                            vec![XmlAttribute::custom("type", "windowreference")],
+                            |builder| {
+                                builder.write_string("class", vec![], &link.class)?;
+                                builder.write_string("recordname", vec![], &link.record_name)
+                            },
+                        )?;
+                        if let Some(map) = map {
+                            process_placements(&encounter_npc.placements, map, builder)?;
+                        }
+                        builder.write_string(
+                            "name",
+                            vec![XmlAttribute::string()],
+                            &encounter_npc.name,
+                        )
+                    })?;
+                }
+                Ok(())
+            })
+        })?;
+        Ok(())
+    }
+}
+
+fn process_placements(
+    placements: &[TokenPlacement],
+    map: &RecordLink,
+    w: &XmlBuilder,
+) -> Result<(), anyhow::Error> {
+    if placements.is_empty() {
+        return Ok(());
+    }
+
+    w.child("maplink", vec![], |builder| {
+        let mut counter = 0;
+        for placement in placements {
+            counter += 1;
+            let id = format!("id-{:05}", counter);
+            builder.child(&id, vec![], |builder| {
+                builder.child(
+                    "imageref",
+                    vec![XmlAttribute::custom("type", "windowreference")],
+                    |builder| {
+                        builder.write_string("class", vec![], &map.class)?;
+                        builder.write_string("recordname", vec![], &map.record_name)
+                    },
+                )?;
+                builder.write_string("imagex", vec![XmlAttribute::number()], placement.x)?;
+                builder.write_string("imagey", vec![XmlAttribute::number()], placement.y)
+            })?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOBLIN: &str = r#"
+name: Goblin
+size: Small
+creature-type: humanoid
+alignment: neutral evil
+armor-class: { value: 15 }
+hit-points: { average: 7 }
+speed: 30 ft.
+abilities: { str: 8, dex: 14, con: 10, int: 10, wis: 8, cha: 8 }
+challenge-rating: 1/4
+"#;
+
+    fn module(encounter: &str) -> FGUModule {
+        let module: ModuleDefinition = serde_yaml::from_str(
+            "{name: Encounters, spell-files: [], table-files: [], source: Encounters, category: Source Book, author: Tests, images: [{name: Cave, file: cave.png}]}",
+        )
+        .unwrap();
+        let mut module = FGUModule::new(module);
+        module.npcs.push(serde_yaml::from_str(GOBLIN).unwrap());
+        module
+            .encounters
+            .push(serde_yaml::from_str(encounter).unwrap());
+        module
+    }
+
+    fn process(module: &FGUModule) -> Result<String, anyhow::Error> {
+        let links = RecordLinks::new(module)?;
+        let builder = XmlBuilder::new();
+        module.encounters[0].process(&module.npcs, &links, &builder)?;
+        let xml = builder.writer.into_inner().unwrap().into_inner();
+        Ok(String::from_utf8(xml)?)
+    }
+
+    fn error(module: &FGUModule) -> String {
+        match process(module) {
+            Ok(_) => panic!("expected the encounter to be rejected"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn totals_the_xp_of_every_npc() {
+        let xml = process(&module(
+            "{name: Ambush, map: Cave, npcs: [{name: Goblin, count: 3, placements: [{x: 10, y: -20}]}]}",
+        ))
+        .unwrap();
+
+        assert!(xml.contains("<exp type=\"number\">150</exp>"), "{}", xml);
+        assert!(xml.contains("<count type=\"number\">3</count>"), "{}", xml);
+        assert!(
+            xml.contains("<imagey type=\"number\">-20</imagey>"),
+            "{}",
+            xml
+        );
+    }
+
+    #[test]
+    fn unknown_npcs_are_rejected() {
+        assert_eq!(
+            error(&module("{name: Ambush, npcs: [{name: Orc, count: 1}]}")),
+            "Encounter Ambush references NPC Orc, which is not in this module"
+        );
+    }
+
+    #[test]
+    fn placements_need_a_map() {
+        assert_eq!(
+            error(&module(
+                "{name: Ambush, npcs: [{name: Goblin, count: 1, placements: [{x: 0, y: 0}]}]}"
+            )),
+            "Encounter Ambush places Goblin tokens without a map"
+        );
+    }
+
+    #[test]
+    fn placements_cant_outnumber_the_npcs() {
+        assert_eq!(
+            error(&module(
+                "{name: Ambush, map: Cave, npcs: [{name: Goblin, count: 1, placements: [{x: 0, y: 0}, {x: 1, y: 1}]}]}"
+            )),
+            "Encounter Ambush places 2 Goblin tokens but only has 1 of them"
+        );
+    }
+
+    #[test]
+    fn unknown_challenge_ratings_are_rejected() {
+        let mut module = module("{name: Ambush, npcs: [{name: Goblin, count: 2}]}");
+        module.npcs[0].challenge_rating = "1/3".to_owned();
+
+        assert_eq!(
+            error(&module),
+            "Encounter Ambush can't total its XP, NPC Goblin has an unknown challenge rating 1/3"
+        );
+    }
+}
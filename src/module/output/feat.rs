@@ -0,0 +1,35 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::*;
+
+impl FeatDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            if let Some(prerequisite) = &self.prerequisite {
+                builder.write_string("prerequisite", vec![XmlAttribute::string()], prerequisite)?;
+            }
+            builder.write_string("source", vec![XmlAttribute::string()], &module.name)?;
+            self.description(links, builder)?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn description(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let description = links.formatted_text(&self.description)?;
+        w.write_raw(
+            "text",
+            vec![XmlAttribute::r#type("formattedtext")],
+            description,
+        )
+    }
+}
@@ -1,13 +1,56 @@
 use crate::module::inputs::*;
+use super::image::archive_path;
+use super::links::RecordLinks;
+use super::tables::check_nested_tables;
+use super::thumbnail::{placeholder_thumbnail, validate_thumbnail};
 use super::*;
+use std::path::PathBuf;
 
 pub struct FGUModule {
     pub module: ModuleDefinition,
     pub spells: Vec<SpellDefinition>,
     pub tables: Vec<TableDefinition>,
+    pub npcs: Vec<NpcDefinition>,
+    pub items: Vec<ItemDefinition>,
+    pub magic_items: Vec<MagicItemDefinition>,
+    pub feats: Vec<FeatDefinition>,
+    pub backgrounds: Vec<BackgroundDefinition>,
+    pub races: Vec<RaceDefinition>,
+    pub classes: Vec<ClassDefinition>,
+    pub subclasses: Vec<SubclassDefinition>,
+    pub encounters: Vec<EncounterDefinition>,
+    pub parcels: Vec<ParcelDefinition>,
+    pub quests: Vec<QuestDefinition>,
+    pub stories: Vec<StoryDefinition>,
+    pub reference_manual: Option<ReferenceManual>,
+    pub thumbnail: Option<Vec<u8>>,
+    pub tokens: Vec<TokenFile>,
 }
 
 impl FGUModule {
+    pub fn new(module: ModuleDefinition) -> Self {
+        Self {
+            module,
+            spells: Vec::new(),
+            tables: Vec::new(),
+            npcs: Vec::new(),
+            items: Vec::new(),
+            magic_items: Vec::new(),
+            feats: Vec::new(),
+            backgrounds: Vec::new(),
+            races: Vec::new(),
+            classes: Vec::new(),
+            subclasses: Vec::new(),
+            encounters: Vec::new(),
+            parcels: Vec::new(),
+            quests: Vec::new(),
+            stories: Vec::new(),
+            reference_manual: None,
+            thumbnail: None,
+            tokens: Vec::new(),
+        }
+    }
+
     pub fn process(&self, destination: &str) -> Result<(), anyhow::Error> {
         use std::fs::File;
         use std::io::prelude::*;
@@ -16,6 +59,13 @@ impl FGUModule {
 
         let client_file = self.client_file()?;
         let definition_file = self.definition_file()?;
+        let thumbnail = match &self.thumbnail {
+            Some(thumbnail) => {
+                validate_thumbnail(thumbnail)?;
+                thumbnail.clone()
+            }
+            None => placeholder_thumbnail(&self.module.name)?,
+        };
 
         let path = Path::new(&destination);
         let file = File::create(&path)?;
@@ -28,6 +78,45 @@ impl FGUModule {
         zip.start_file("definition.xml", Default::default())?;
         zip.write_all(definition_file.as_bytes())?;
 
+        zip.start_file("thumbnail.png", Default::default())?;
+        zip.write_all(&thumbnail)?;
+
+        let mut images: Vec<&Path> = self
+            .module
+            .images
+            .iter()
+            .map(|image| image.path.as_path())
+            .collect();
+        if let Some(manual) = &self.reference_manual {
+            images.extend(manual.images());
+        }
+
+        for token in &self.tokens {
+            zip.start_file(&token.archive_path, Default::default())?;
+            zip.write_all(&std::fs::read(&token.path)?)?;
+        }
+
+        // images are stored by file name, so only the same file may be packed twice
+        let mut packed_images: Vec<(String, PathBuf)> = Vec::new();
+        for image in images {
+            let image_path = archive_path(image);
+            let source = std::fs::canonicalize(image)?;
+            if let Some((_, packed)) = packed_images.iter().find(|(path, _)| *path == image_path) {
+                if *packed == source {
+                    continue;
+                }
+                anyhow::bail!(
+                    "Images {} and {} would both be stored as {}, rename one of them",
+                    packed.display(),
+                    source.display(),
+                    image_path
+                );
+            }
+            zip.start_file(&image_path, Default::default())?;
+            zip.write_all(&std::fs::read(image)?)?;
+            packed_images.push((image_path, source));
+        }
+
         zip.finish()?;
 
         Ok(())
@@ -44,7 +133,11 @@ impl FGUModule {
             ],
             |builder| {
                 builder.write_string("name", vec![XmlAttribute::string()], &self.module.name)?;
-                builder.write_string("category", vec![XmlAttribute::string()], "Source Book")?;
+                builder.write_string(
+                    "category",
+                    vec![XmlAttribute::string()],
+                    self.module.category.to_name(),
+                )?;
                 builder.write_string(
                     "author",
                     vec![XmlAttribute::string()],
@@ -64,6 +157,21 @@ impl FGUModule {
 
         let has_spells = !self.spells.is_empty();
         let has_tables = !self.tables.is_empty();
+        let has_npcs = !self.npcs.is_empty();
+        let has_items = !self.items.is_empty();
+        let has_magic_items = !self.magic_items.is_empty();
+        let has_feats = !self.feats.is_empty();
+        let has_backgrounds = !self.backgrounds.is_empty();
+        let has_races = !self.races.is_empty();
+        let has_classes = !self.classes.is_empty();
+        let has_subclasses = !self.subclasses.is_empty();
+        let has_stories = !self.stories.is_empty();
+        let has_encounters = !self.encounters.is_empty();
+        let has_parcels = !self.parcels.is_empty();
+        let has_quests = !self.quests.is_empty();
+        let has_images = !self.module.images.is_empty();
+        let links = RecordLinks::new(self)?;
+        check_nested_tables(&self.tables)?;
 
         xml_builder.child(
             "root",
@@ -90,85 +198,75 @@ impl FGUModule {
                             )?;
                             xml_builder.child("entries", vec![], |_| {
                                 if has_spells {
-                                    xml_builder.child(
-                                        "spell",
-                                        vec![XmlAttribute::custom("static", "true")],
-                                        |_| {
-                                            xml_builder.child(
-                                                "librarylink",
-                                                vec![XmlAttribute::custom(
-                                                    "type",
-                                                    "windowreference",
-                                                )],
-                                                |_| {
-                                                    xml_builder.write_string(
-                                                        "class",
-                                                        vec![],
-                                                        "reference_list",
-                                                    )?;
-                                                    xml_builder.write_string(
-                                                        "recordname",
-                                                        vec![],
-                                                        "..",
-                                                    )?;
-                                                    Ok(())
-                                                },
-                                            )?;
-
-                                            xml_builder.write_string(
-                                                "name",
-                                                vec![XmlAttribute::string()],
-                                                "Spells",
-                                            )?;
-                                            xml_builder.write_string(
-                                                "recordtype",
-                                                vec![XmlAttribute::string()],
-                                                "spell",
-                                            )?;
-                                            Ok(())
-                                        },
-                                    )?;
+                                    library_entry(&xml_builder, "spell", "Spells", "spell")?;
                                 }
                                 if has_tables {
-                                    xml_builder.child(
-                                        "tables",
-                                        vec![XmlAttribute::custom("static", "true")],
-                                        |_| {
-                                            xml_builder.child(
-                                                "librarylink",
-                                                vec![XmlAttribute::custom(
-                                                    "type",
-                                                    "windowreference",
-                                                )],
-                                                |_| {
-                                                    xml_builder.write_string(
-                                                        "class",
-                                                        vec![],
-                                                        "reference_list",
-                                                    )?;
-                                                    xml_builder.write_string(
-                                                        "recordname",
-                                                        vec![],
-                                                        "..",
-                                                    )?;
-                                                    Ok(())
-                                                },
-                                            )?;
-
-                                            xml_builder.write_string(
-                                                "name",
-                                                vec![XmlAttribute::string()],
-                                                "Tables",
-                                            )?;
-                                            xml_builder.write_string(
-                                                "recordtype",
-                                                vec![XmlAttribute::string()],
-                                                "tables",
-                                            )?;
-                                            Ok(())
-                                        },
+                                    library_entry(&xml_builder, "tables", "Tables", "tables")?;
+                                }
+                                if has_npcs {
+                                    library_entry(&xml_builder, "npc", "NPCs", "npc")?;
+                                }
+                                if has_items {
+                                    library_entry(&xml_builder, "item", "Items", "item")?;
+                                }
+                                if has_magic_items {
+                                    // the magic item view only lists the items with a rarity
+                                    library_view_entry(
+                                        &xml_builder,
+                                        "magicitems",
+                                        "Magic Items",
+                                        "item",
+                                        Some("magicitems"),
+                                    )?;
+                                }
+                                if has_feats {
+                                    library_entry(&xml_builder, "feat", "Feats", "feat")?;
+                                }
+                                if has_backgrounds {
+                                    library_entry(
+                                        &xml_builder,
+                                        "background",
+                                        "Backgrounds",
+                                        "background",
+                                    )?;
+                                }
+                                if has_races {
+                                    library_entry(&xml_builder, "race", "Races", "race")?;
+                                }
+                                if has_classes {
+                                    library_entry(&xml_builder, "class", "Classes", "class")?;
+                                }
+                                if has_subclasses {
+                                    library_entry(
+                                        &xml_builder,
+                                        "class_specialization",
+                                        "Subclasses",
+                                        "class_specialization",
+                                    )?;
+                                }
+                                if has_stories {
+                                    library_entry(&xml_builder, "story", "Story", "story")?;
+                                }
+                                if has_encounters {
+                                    library_entry(&xml_builder, "battle", "Encounters", "battle")?;
+                                }
+                                if has_parcels {
+                                    library_entry(
+                                        &xml_builder,
+                                        "treasureparcels",
+                                        "Treasure Parcels",
+                                        "treasureparcels",
                                     )?;
                                 }
+                                if has_quests {
+                                    library_entry(&xml_builder, "quest", "Quests", "quest")?;
+                                }
+                                if has_images {
+                                    library_entry(&xml_builder, "image", "Images", "image")?;
+                                }
+                                if let Some(manual) = &self.reference_manual {
+                                    self.manual_library_entry(manual, &xml_builder)?;
+                                }
                                 Ok(())
                             })
                         },
@@ -178,7 +276,7 @@ impl FGUModule {
                 if has_spells {
                     xml_builder.child("spell", vec![], |_| {
                         for spell in &self.spells {
-                            spell.process(&self.module, &xml_builder)?;
+                            spell.process(&self.module, &links, &xml_builder)?;
                         }
                         Ok(())
                     })?;
@@ -187,16 +285,877 @@ impl FGUModule {
                 if has_tables {
                     xml_builder.child("tables", vec![], |_| {
                         for table in &self.tables {
-                            table.process(&xml_builder)?;
+                            table.process(&links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_npcs {
+                    xml_builder.child("npc", vec![], |_| {
+                        for npc in &self.npcs {
+                            npc.process(&self.module, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_items || has_magic_items {
+                    xml_builder.child("item", vec![], |_| {
+                        for item in &self.items {
+                            item.process(&self.module, &links, &xml_builder)?;
+                        }
+                        for magic_item in &self.magic_items {
+                            magic_item.process(&self.module, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_feats {
+                    xml_builder.child("feat", vec![], |_| {
+                        for feat in &self.feats {
+                            feat.process(&self.module, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_backgrounds {
+                    xml_builder.child("background", vec![], |_| {
+                        for background in &self.backgrounds {
+                            background.process(&self.module, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_races {
+                    xml_builder.child("race", vec![], |_| {
+                        for race in &self.races {
+                            race.process(&self.module, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_classes {
+                    xml_builder.child("class", vec![], |_| {
+                        for class in &self.classes {
+                            class.process(&self.module, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_subclasses {
+                    xml_builder.child("class_specialization", vec![], |_| {
+                        for subclass in &self.subclasses {
+                            subclass.process(&self.module, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_stories {
+                    xml_builder.child("encounter", vec![], |_| {
+                        let mut categories: Vec<&str> = Vec::new();
+                        for story in &self.stories {
+                            if !categories.contains(&story.category.as_str()) {
+                                categories.push(&story.category);
+                            }
+                        }
+
+                        for category in categories {
+                            xml_builder.child(
+                                "category",
+                                vec![
+                                    XmlAttribute::custom("name", category),
+                                    XmlAttribute::custom("baseicon", "0"),
+                                    XmlAttribute::custom("decalicon", "0"),
+                                ],
+                                |_| {
+                                    for story in &self.stories {
+                                        if story.category == category {
+                                            story.process(&links, &xml_builder)?;
+                                        }
+                                    }
+                                    Ok(())
+                                },
+                            )?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_encounters {
+                    xml_builder.child("battle", vec![], |_| {
+                        for encounter in &self.encounters {
+                            encounter.process(&self.npcs, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_parcels {
+                    xml_builder.child("treasureparcels", vec![], |_| {
+                        for parcel in &self.parcels {
+                            parcel.process(self, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_quests {
+                    xml_builder.child("quest", vec![], |_| {
+                        for quest in &self.quests {
+                            quest.process(&self.module, &links, &xml_builder)?;
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                if has_images {
+                    xml_builder.child("image", vec![], |_| {
+                        for image in &self.module.images {
+                            image.process(&xml_builder)?;
                         }
                         Ok(())
                     })?;
                 }
 
+                if let Some(manual) = &self.reference_manual {
+                    manual.process(&self.module, &links, &xml_builder)?;
+                }
+
                 Ok(())
             },
         )?;
 
         xml_builder.into_string()
     }
-}
\ No newline at end of file
+
+    fn manual_library_entry(
+        &self,
+        manual: &ReferenceManual,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        w.child(
+            "refmanual",
+            vec![XmlAttribute::custom("static", "true")],
+            |builder| {
+                builder.child(
+                    "librarylink",
+                    vec![XmlAttribute::custom("type", "windowreference")],
+                    |builder| {
+                        builder.write_string("class", vec![], "reference_manual")?;
+                        builder.write_string(
+                            "recordname",
+                            vec![],
+                            format!("reference.refmanualindex@{}", self.module.name),
+                        )
+                    },
+                )?;
+                builder.write_string("name", vec![XmlAttribute::string()], &manual.name)
+            },
+        )
+    }
+}
+
+fn library_entry(
+    w: &XmlBuilder,
+    entry_name: &str,
+    display_name: &str,
+    record_type: &str,
+) -> Result<(), anyhow::Error> {
+    library_view_entry(w, entry_name, display_name, record_type, None)
+}
+
+/// A library entry listing the records of a type, narrowed down to one of the ruleset's
+/// views of them when a view is given.
+fn library_view_entry(
+    w: &XmlBuilder,
+    entry_name: &str,
+    display_name: &str,
+    record_type: &str,
+    record_view: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    w.child(
+        entry_name,
+        vec![XmlAttribute::custom("static", "true")],
+        |builder| {
+            builder.child(
+                "librarylink",
+                vec![XmlAttribute::custom("type", "windowreference")],
+                |builder| {
+                    builder.write_string("class", vec![], "reference_list")?;
+                    builder.write_string("recordname", vec![], "..")?;
+                    Ok(())
+                },
+            )?;
+
+            builder.write_string("name", vec![XmlAttribute::string()], display_name)?;
+            builder.write_string("recordtype", vec![XmlAttribute::string()], record_type)?;
+            if let Some(record_view) = record_view {
+                builder.write_string("recordview", vec![XmlAttribute::string()], record_view)?;
+            }
+            Ok(())
+        },
+    )
+}
+#[cfg(test)]
+mod tests {
+    use super::image::encode_png;
+    use super::*;
+    use crate::module::extract::XmlElement;
+    use std::io::Read;
+
+    fn module(name: &str) -> FGUModule {
+        FGUModule::new(ModuleDefinition {
+            name: name.to_owned(),
+            source: name.to_owned(),
+            author: "Tests".to_owned(),
+            ..Default::default()
+        })
+    }
+
+    fn record<T: serde::de::DeserializeOwned>(yaml: &str) -> T {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    /// Builds the module and reads its client.xml back.
+    fn build(module: &FGUModule) -> Result<XmlElement, anyhow::Error> {
+        let path = std::env::temp_dir().join(format!(
+            "fguctl-{}-{}.mod",
+            module.module.name,
+            std::process::id()
+        ));
+        let client = module.process(path.to_str().unwrap()).and_then(|_| {
+            let mut archive = zip::ZipArchive::new(std::fs::File::open(&path)?)?;
+            let mut xml = String::new();
+            archive.by_name("client.xml")?.read_to_string(&mut xml)?;
+            XmlElement::parse(&xml)
+        });
+        let _ = std::fs::remove_file(&path);
+        client
+    }
+
+    fn error(module: &FGUModule) -> String {
+        match build(module) {
+            Ok(_) => panic!("expected the build to fail"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    /// The only record of a node such as `feat`.
+    fn only_record<'a>(client: &'a XmlElement, node: &str) -> &'a XmlElement {
+        let records = client.child(node).unwrap().records();
+        assert_eq!(records.len(), 1, "{} should hold one record", node);
+        records[0]
+    }
+
+    /// The library entry listing a record type, checked against its name and type.
+    fn library_entry<'a>(
+        client: &'a XmlElement,
+        entry: &str,
+        display_name: &str,
+        record_type: &str,
+    ) -> &'a XmlElement {
+        let library = client.child("library").unwrap().elements().next().unwrap();
+        let entry = library.child("entries").unwrap().child(entry).unwrap();
+        assert_eq!(entry.child_text("name").as_deref(), Some(display_name));
+        assert_eq!(entry.child_text("recordtype").as_deref(), Some(record_type));
+        entry
+    }
+
+    const GOBLIN: &str = r#"
+name: Goblin
+size: Small
+creature-type: humanoid
+alignment: neutral evil
+armor-class: { value: 15, description: leather armor }
+hit-points: { average: 7, dice: 2d6 }
+speed: 30 ft.
+abilities: { str: 8, dex: 14, con: 10, int: 10, wis: 8, cha: 8 }
+challenge-rating: 1/4
+actions:
+  - name: Scimitar
+    description: Melee Weapon Attack
+"#;
+
+    #[test]
+    fn builds_npcs() {
+        let mut module = module("Npcs");
+        module.npcs.push(record(GOBLIN));
+        let mut boss: NpcDefinition = record(GOBLIN);
+        boss.name = "Goblin Boss".to_owned();
+        boss.saving_throws = vec![record("{ability: dex, bonus: 4}")];
+        boss.skills = vec![record("{skill: Stealth, bonus: 6}")];
+        module.npcs.push(boss);
+        let client = build(&module).unwrap();
+
+        library_entry(&client, "npc", "NPCs", "npc");
+        let npcs = client.child("npc").unwrap().records();
+        let goblin = npcs[0];
+        assert_eq!(goblin.child_text("name").as_deref(), Some("Goblin"));
+        assert_eq!(goblin.child_number("ac"), Some(15));
+        assert_eq!(
+            goblin.child_text("actext").as_deref(),
+            Some("(leather armor)")
+        );
+        assert_eq!(goblin.child_number("xp"), Some(50));
+        assert!(goblin.child("actions").is_some());
+        // empty saves and skills aren't written at all
+        assert!(goblin.child("savingthrows").is_none());
+        assert!(goblin.child("skills").is_none());
+
+        let boss = npcs[1];
+        assert_eq!(boss.child_text("savingthrows").as_deref(), Some("Dex +4"));
+        assert_eq!(boss.child_text("skills").as_deref(), Some("Stealth +6"));
+    }
+
+    #[test]
+    fn builds_magic_items() {
+        let mut module = module("MagicItems");
+        module.magic_items.push(record(
+            r#"
+name: Wand of Sparks
+kind: { type: other, item-type: Wand }
+rarity: rare
+attunement: { type: required-by, by: a wizard }
+charges: 7
+powers:
+  - name: Spark
+    description: Expend 1 charge.
+    actions:
+      damages:
+        - damage:
+            - modifier: { damage-mod: none }
+              damage-type: lightning
+              dice: [{ dice-type: d6, count: 2 }]
+"#,
+        ));
+        let client = build(&module).unwrap();
+
+        let entry = library_entry(&client, "magicitems", "Magic Items", "item");
+        assert_eq!(
+            entry.child_text("recordview").as_deref(),
+            Some("magicitems")
+        );
+        let records = client.child("item").unwrap().records();
+        let wand = records[0];
+        assert_eq!(wand.child_text("name").as_deref(), Some("Wand of Sparks"));
+        assert_eq!(wand.child_number("isidentified"), Some(0));
+        assert_eq!(wand.child_text("nonid_name").as_deref(), Some("Wand"));
+        assert_eq!(wand.child_text("type").as_deref(), Some("Wand"));
+        assert_eq!(wand.child_number("prepared"), Some(7));
+        assert_eq!(
+            wand.child_text("rarity").as_deref(),
+            Some("Rare (requires attunement by a wizard)")
+        );
+
+        let power = wand.child("powers").unwrap().elements().next().unwrap();
+        assert_eq!(power.child_text("name").as_deref(), Some("Spark"));
+        let action = power.child("actions").unwrap().elements().next().unwrap();
+        assert_eq!(action.child_text("type").as_deref(), Some("damage"));
+        let damage = action
+            .child("damagelist")
+            .unwrap()
+            .elements()
+            .next()
+            .unwrap();
+        assert_eq!(damage.child_text("dice").as_deref(), Some("2d6"));
+        assert_eq!(damage.child_text("type").as_deref(), Some("lightning"));
+    }
+
+    #[test]
+    fn builds_items() {
+        let mut module = module("Items");
+        module.items.push(record(
+            r#"
+name: Longsword
+kind:
+  type: weapon
+  category: Martial Melee
+  damage: [{ dice-type: d8, count: 1 }]
+  damage-type: slashing
+  properties: [Versatile (1d10)]
+cost: 15 gp
+weight: 3
+"#,
+        ));
+        module.items.push(record(
+            r#"
+name: Half Plate
+kind:
+  type: armor
+  category: Medium
+  armor-class: 15
+  dex-bonus: { type: limited, max: 2 }
+  stealth-disadvantage: true
+"#,
+        ));
+        let client = build(&module).unwrap();
+
+        library_entry(&client, "item", "Items", "item");
+        let items = client.child("item").unwrap().records();
+        let sword = items[0];
+        assert_eq!(sword.child_text("name").as_deref(), Some("Longsword"));
+        assert_eq!(sword.child_number("isidentified"), Some(1));
+        assert_eq!(sword.child_text("type").as_deref(), Some("Weapon"));
+        assert_eq!(
+            sword.child_text("subtype").as_deref(),
+            Some("Martial Melee")
+        );
+        assert_eq!(sword.child_text("damage").as_deref(), Some("1d8 slashing"));
+        assert_eq!(
+            sword.child_text("properties").as_deref(),
+            Some("Versatile (1d10)")
+        );
+        assert_eq!(sword.child_text("cost").as_deref(), Some("15 gp"));
+        assert_eq!(sword.child_number("weight"), Some(3));
+
+        let armor = items[1];
+        assert_eq!(armor.child_text("type").as_deref(), Some("Armor"));
+        assert_eq!(armor.child_number("ac"), Some(15));
+        assert_eq!(armor.child_text("dexbonus").as_deref(), Some("Yes (max 2)"));
+        assert_eq!(armor.child_text("stealth").as_deref(), Some("Disadvantage"));
+    }
+
+    #[test]
+    fn builds_feats() {
+        let mut module = module("Feats");
+        module.feats.push(record(
+            "{name: Alert, prerequisite: Dexterity 13 or higher, description: Always on the lookout.}",
+        ));
+        let client = build(&module).unwrap();
+
+        library_entry(&client, "feat", "Feats", "feat");
+        let feat = only_record(&client, "feat");
+        assert_eq!(feat.child_text("name").as_deref(), Some("Alert"));
+        assert_eq!(
+            feat.child_text("prerequisite").as_deref(),
+            Some("Dexterity 13 or higher")
+        );
+        assert_eq!(feat.child_text("source").as_deref(), Some("Feats"));
+        assert!(feat
+            .child_text("text")
+            .unwrap()
+            .contains("Always on the lookout."));
+    }
+
+    #[test]
+    fn builds_backgrounds() {
+        let mut module = module("Backgrounds");
+        module.backgrounds.push(record(
+            r#"
+name: Sage
+skill-proficiencies: [Arcana, History]
+tool-proficiencies: [Calligrapher's supplies]
+languages: [Two of your choice]
+equipment: [A bottle of ink, A quill]
+features:
+  - name: Researcher
+    description: You know where to look.
+"#,
+        ));
+        let client = build(&module).unwrap();
+
+        library_entry(&client, "background", "Backgrounds", "background");
+        let sage = only_record(&client, "background");
+        assert_eq!(sage.child_text("name").as_deref(), Some("Sage"));
+        assert_eq!(sage.child_text("skill").as_deref(), Some("Arcana, History"));
+        assert_eq!(
+            sage.child_text("tool").as_deref(),
+            Some("Calligrapher's supplies")
+        );
+        assert_eq!(
+            sage.child_text("languages").as_deref(),
+            Some("Two of your choice")
+        );
+        assert_eq!(
+            sage.child_text("equipment").as_deref(),
+            Some("A bottle of ink, A quill")
+        );
+        let feature = sage.child("features").unwrap().elements().next().unwrap();
+        assert_eq!(feature.child_text("name").as_deref(), Some("Researcher"));
+        // nothing to describe, so no text is written
+        assert!(sage.child("text").is_none());
+    }
+
+    #[test]
+    fn builds_races() {
+        let mut module = module("Races");
+        module.races.push(record(
+            r#"
+name: Dwarf
+ability-score-increases: [{ ability: con, increase: 2 }]
+size: Medium
+speed: 25
+traits:
+  - name: Darkvision
+    description: You see in the dark.
+subraces:
+  - name: Hill Dwarf
+    ability-score-increases: [{ ability: wis, increase: 1 }]
+"#,
+        ));
+        let client = build(&module).unwrap();
+
+        library_entry(&client, "race", "Races", "race");
+        let dwarf = only_record(&client, "race");
+        assert_eq!(dwarf.child_text("name").as_deref(), Some("Dwarf"));
+        let traits: Vec<String> = dwarf
+            .child("traits")
+            .unwrap()
+            .elements()
+            .filter_map(|race_trait| race_trait.child_text("name"))
+            .collect();
+        assert_eq!(
+            traits,
+            vec!["Ability Score Increase", "Size", "Speed", "Darkvision"]
+        );
+        let speed = dwarf.child("traits").unwrap().elements().nth(2).unwrap();
+        assert_eq!(
+            speed.child_text("text").as_deref(),
+            Some("Your base walking speed is 25 feet.")
+        );
+
+        let subrace = dwarf.child("subraces").unwrap().elements().next().unwrap();
+        assert_eq!(subrace.child_text("name").as_deref(), Some("Hill Dwarf"));
+        let increase = subrace.child("traits").unwrap().elements().next().unwrap();
+        assert_eq!(
+            increase.child_text("text").as_deref(),
+            Some("Your Wisdom score increases by 1.")
+        );
+    }
+
+    const PALADIN: &str = r#"
+name: Paladin
+hit-die: 1d10
+proficiencies:
+  armor: All armor, shields
+  saving-throws: [wis, cha]
+spellcasting: half
+features:
+  - { level: 1, name: Divine Sense, description: You sense evil. }
+subclasses:
+  - name: Oath of Devotion
+    level: 3
+    features:
+      - { level: 3, name: Sacred Weapon, description: Your weapon shines. }
+"#;
+
+    #[test]
+    fn builds_classes_and_subclasses() {
+        let mut module = module("Classes");
+        module.classes.push(record(PALADIN));
+        module.subclasses.push(record(
+            r#"
+name: Oath of Vengeance
+class: Paladin
+level: 3
+features:
+  - { level: 3, name: Vow of Enmity, description: You mark a foe. }
+"#,
+        ));
+        let client = build(&module).unwrap();
+
+        library_entry(&client, "class", "Classes", "class");
+        let paladin = only_record(&client, "class");
+        assert_eq!(paladin.child_text("name").as_deref(), Some("Paladin"));
+        assert_eq!(paladin.child_number("casterlevelinvmult"), Some(2));
+        let hit_dice = paladin.child("hitdice").unwrap();
+        assert_eq!(hit_dice.child_text("dice").as_deref(), Some("1d10"));
+        let saves = paladin
+            .child("proficiencies")
+            .unwrap()
+            .child("savingthrows")
+            .unwrap();
+        assert_eq!(
+            saves.child_text("text").as_deref(),
+            Some("Wisdom, Charisma")
+        );
+        let ability = paladin
+            .child("abilities")
+            .unwrap()
+            .elements()
+            .next()
+            .unwrap();
+        assert_eq!(
+            ability.child_text("name").as_deref(),
+            Some("Oath of Devotion")
+        );
+        // subclass features follow the class's, marked with their subclass
+        let features: Vec<&XmlElement> = paladin.child("features").unwrap().elements().collect();
+        assert_eq!(features.len(), 2);
+        assert!(features[0].child("specialization").is_none());
+        assert_eq!(
+            features[1].child_text("specialization").as_deref(),
+            Some("Oath of Devotion")
+        );
+
+        library_entry(
+            &client,
+            "class_specialization",
+            "Subclasses",
+            "class_specialization",
+        );
+        let vengeance = only_record(&client, "class_specialization");
+        assert_eq!(vengeance.child_text("class").as_deref(), Some("Paladin"));
+        assert_eq!(vengeance.child_number("level"), Some(3));
+        let feature = vengeance
+            .child("features")
+            .unwrap()
+            .elements()
+            .next()
+            .unwrap();
+        assert_eq!(
+            feature.child_text("specialization").as_deref(),
+            Some("Oath of Vengeance")
+        );
+    }
+
+    #[test]
+    fn standalone_subclasses_need_a_class() {
+        let mut module = module("Subclasses");
+        module
+            .subclasses
+            .push(record("{name: Oath of Vengeance, level: 3}"));
+
+        assert_eq!(
+            error(&module),
+            "Subclass Oath of Vengeance must name the class it belongs to"
+        );
+    }
+
+    #[test]
+    fn builds_stories_by_category() {
+        let mut module = module("Stories");
+        module.stories.push(StoryDefinition::from_markdown(
+            "Chapter 1",
+            "arrival",
+            "# The Village\n\nFog rolls in.",
+        ));
+        module.stories.push(StoryDefinition::from_markdown(
+            "Chapter 2",
+            "the-road",
+            "The road goes on.",
+        ));
+        let client = build(&module).unwrap();
+
+        library_entry(&client, "story", "Story", "story");
+        let categories: Vec<&XmlElement> = client.child("encounter").unwrap().elements().collect();
+        let names: Vec<Option<&str>> = categories
+            .iter()
+            .map(|category| category.attribute("name"))
+            .collect();
+        assert_eq!(names, vec![Some("Chapter 1"), Some("Chapter 2")]);
+
+        let village = categories[0].records()[0];
+        assert_eq!(village.child_text("name").as_deref(), Some("The Village"));
+        assert_eq!(village.child_text("text").as_deref(), Some("Fog rolls in."));
+        // without a heading the file name names the entry
+        let road = categories[1].records()[0];
+        assert_eq!(road.child_text("name").as_deref(), Some("the-road"));
+    }
+
+    #[test]
+    fn builds_a_reference_manual() {
+        let dir = std::env::temp_dir().join(format!("fguctl-manual-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let map = encode_png(40, 30, &[0; 40 * 30 * 3]).unwrap();
+        std::fs::write(dir.join("map.png"), map).unwrap();
+        let chapter = ReferenceChapter::from_markdown(
+            &dir.join("intro.md"),
+            "# Introduction\n## Welcome\n### The Valley\nMist everywhere.\n![The valley](map.png)\n",
+        );
+        let mut module = module("Manual");
+        module.reference_manual = Some(ReferenceManual {
+            name: "Players Guide".to_owned(),
+            chapters: vec![chapter],
+        });
+        let client = build(&module);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let client = client.unwrap();
+
+        let library = client.child("library").unwrap().elements().next().unwrap();
+        let entry = library
+            .child("entries")
+            .unwrap()
+            .child("refmanual")
+            .unwrap();
+        assert_eq!(entry.child_text("name").as_deref(), Some("Players Guide"));
+
+        let reference = client.child("reference").unwrap();
+        let index = reference.child("refmanualindex").unwrap();
+        let chapter = index
+            .child("chapters")
+            .unwrap()
+            .child("chapter_00001")
+            .unwrap();
+        assert_eq!(chapter.child_text("name").as_deref(), Some("Introduction"));
+        let page = reference
+            .child("refmanualdata")
+            .unwrap()
+            .child("refpage_00001")
+            .unwrap();
+        assert_eq!(page.child_text("name").as_deref(), Some("The Valley"));
+        let blocks: Vec<&XmlElement> = page.child("blocks").unwrap().elements().collect();
+        assert_eq!(
+            blocks[0].child_text("blocktype").as_deref(),
+            Some("singletext")
+        );
+        assert_eq!(blocks[1].child_text("blocktype").as_deref(), Some("image"));
+        assert_eq!(
+            blocks[1].child_text("caption").as_deref(),
+            Some("The valley")
+        );
+        assert_eq!(blocks[1].child_text("size").as_deref(), Some("40,30"));
+        let image = blocks[1].child("image").unwrap();
+        assert_eq!(
+            image.child_text("bitmap").as_deref(),
+            Some("images/map.png")
+        );
+    }
+
+    #[test]
+    fn reference_manual_images_must_exist() {
+        let path = std::env::temp_dir().join("fguctl-missing").join("intro.md");
+        let chapter =
+            ReferenceChapter::from_markdown(&path, "## Welcome\n### Maps\n![Lost](lost.png)\n");
+        let mut module = module("MissingImage");
+        module.reference_manual = Some(ReferenceManual {
+            name: "Players Guide".to_owned(),
+            chapters: vec![chapter],
+        });
+
+        assert_eq!(
+            error(&module),
+            format!(
+                "Unable to read reference manual image {}",
+                path.with_file_name("lost.png").display()
+            )
+        );
+    }
+
+    #[test]
+    fn builds_and_packs_images() {
+        let dir = std::env::temp_dir().join(format!("fguctl-images-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("other")).unwrap();
+        let png = encode_png(40, 30, &[0; 40 * 30 * 3]).unwrap();
+        std::fs::write(dir.join("cave.png"), &png).unwrap();
+        std::fs::write(dir.join("other").join("cave.png"), &png).unwrap();
+
+        let mut module = module("Images");
+        let mut cave: ImageDefinition =
+            record("{name: Cave, file: cave.png, grid: {size: 50, offset-x: 5}}");
+        cave.path = dir.join("cave.png");
+        module.module.images.push(cave);
+        let client = build(&module);
+
+        let mut clash = module;
+        let mut other: ImageDefinition = record("{name: Other Cave, file: other/cave.png}");
+        other.path = dir.join("other").join("cave.png");
+        clash.module.images.push(other);
+        let clash = error(&clash);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let client = client.unwrap();
+        library_entry(&client, "image", "Images", "image");
+        let cave = only_record(&client, "image");
+        assert_eq!(cave.child_text("name").as_deref(), Some("Cave"));
+        let image = cave.child("image").unwrap();
+        assert_eq!(image.child_text("gridsize").as_deref(), Some("50,50"));
+        assert_eq!(image.child_text("gridoffset").as_deref(), Some("5,0"));
+        let layer = image.child("layers").unwrap().child("layer").unwrap();
+        assert_eq!(
+            layer.child_text("bitmap").as_deref(),
+            Some("images/cave.png")
+        );
+
+        assert!(
+            clash.ends_with("would both be stored as images/cave.png, rename one of them"),
+            "{}",
+            clash
+        );
+    }
+
+    #[test]
+    fn builds_parcels() {
+        let mut module = module("Parcels");
+        module.items.push(record(
+            "{name: Rope, kind: {type: adventuring-gear}, cost: 1 gp}",
+        ));
+        module.parcels.push(record(
+            "{name: Goblin Hoard, coins: {gp: 12, cp: 40}, items: [{item: Rope, count: 2}, {name: A bent key}]}",
+        ));
+        let client = build(&module).unwrap();
+
+        library_entry(
+            &client,
+            "treasureparcels",
+            "Treasure Parcels",
+            "treasureparcels",
+        );
+        let hoard = only_record(&client, "treasureparcels");
+        assert_eq!(hoard.child_text("name").as_deref(), Some("Goblin Hoard"));
+        let coins: Vec<(String, u32)> = hoard
+            .child("coinlist")
+            .unwrap()
+            .elements()
+            .map(|coin| {
+                (
+                    coin.child_text("description").unwrap(),
+                    coin.child_number("amount").unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(coins, vec![("GP".to_owned(), 12), ("CP".to_owned(), 40)]);
+
+        let items: Vec<&XmlElement> = hoard.child("itemlist").unwrap().elements().collect();
+        // items from the module are copied in whole
+        assert_eq!(items[0].child_text("name").as_deref(), Some("Rope"));
+        assert_eq!(items[0].child_number("count"), Some(2));
+        assert_eq!(items[0].child_text("cost").as_deref(), Some("1 gp"));
+        assert_eq!(items[1].child_text("name").as_deref(), Some("A bent key"));
+        assert_eq!(items[1].child_number("count"), Some(1));
+    }
+
+    #[test]
+    fn parcels_cant_hold_unknown_items() {
+        let mut module = module("UnknownParcelItem");
+        module
+            .parcels
+            .push(record("{name: Goblin Hoard, items: [{item: Lantern}]}"));
+
+        assert_eq!(
+            error(&module),
+            "Parcel Goblin Hoard references item Lantern, which is not in this module"
+        );
+    }
+
+    #[test]
+    fn builds_quests() {
+        let mut module = module("Quests");
+        module.npcs.push(record(GOBLIN));
+        module.quests.push(record(
+            "{name: Clear the Cave, xp: 300, description: \"Drive out the [[npc:Goblin]] band.\"}",
+        ));
+        let client = build(&module).unwrap();
+
+        library_entry(&client, "quest", "Quests", "quest");
+        let quest = only_record(&client, "quest");
+        assert_eq!(quest.child_text("name").as_deref(), Some("Clear the Cave"));
+        assert_eq!(quest.child_number("xp"), Some(300));
+        let link = quest
+            .child("description")
+            .unwrap()
+            .child("linklist")
+            .unwrap()
+            .child("link")
+            .unwrap();
+        assert_eq!(link.attribute("class"), Some("npc"));
+        assert_eq!(link.text(), "Goblin");
+    }
+}
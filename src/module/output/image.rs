@@ -0,0 +1,187 @@
+use crate::module::inputs::*;
+use super::*;
+use std::path::Path;
+
+impl ImageDefinition {
+    pub fn process(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            builder.child("image", vec![XmlAttribute::r#type("image")], |builder| {
+                if let Some(grid) = &self.grid {
+                    builder.write_string("grid", vec![], "on")?;
+                    builder.write_string(
+                        "gridsize",
+                        vec![],
+                        format!("{},{}", grid.size, grid.size),
+                    )?;
+                    builder.write_string(
+                        "gridoffset",
+                        vec![],
+                        format!("{},{}", grid.offset_x, grid.offset_y),
+                    )?;
+                }
+                builder.child("layers", vec![], |builder| {
+                    builder.child("layer", vec![], |builder| {
+                        builder.write_string(
+                            "name",
+                            vec![],
+                            self.path.file_name().unwrap().to_string_lossy(),
+                        )?;
+                        builder.write_string("id", vec![], 0)?;
+                        builder.write_string("parentid", vec![], -1)?;
+                        builder.write_string("type", vec![], "image")?;
+                        builder.write_string("bitmap", vec![], archive_path(&self.path))
+                    })
+                })
+            })?;
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)
+        })?;
+        Ok(())
+    }
+}
+
+/// Where an image file is stored inside the module archive.
+pub fn archive_path(path: &Path) -> String {
+    format!("images/{}", path.file_name().unwrap().to_string_lossy())
+}
+
+/// Encodes 8-bit RGB pixels, row by row, as a PNG file.
+pub fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Result<Vec<u8>, anyhow::Error> {
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let row_length = width as usize * 3;
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    for row in pixels.chunks(row_length).take(height as usize) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bit depth, truecolor, default compression, filter and no interlace
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_png_chunk(&mut png, b"IHDR", &header);
+    write_png_chunk(&mut png, b"IDAT", &encoder.finish()?);
+    write_png_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn write_png_chunk(png: &mut Vec<u8>, chunk_type: &[u8], data: &[u8]) {
+    let mut crc = flate2::Crc::new();
+    crc.update(chunk_type);
+    crc.update(data);
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(chunk_type);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+/// Reads the pixel dimensions from a PNG or JPEG header.
+pub fn image_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return png_size(bytes);
+    }
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        return jpeg_size(bytes);
+    }
+    None
+}
+
+fn png_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    if bytes.len() < 24 || &bytes[12..16] != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes([bytes[16], bytes[17], bytes[18], bytes[19]]);
+    let height = u32::from_be_bytes([bytes[20], bytes[21], bytes[22], bytes[23]]);
+    Some((width, height))
+}
+
+fn jpeg_size(bytes: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+    while offset + 9 < bytes.len() {
+        if bytes[offset] != 0xFF {
+            return None;
+        }
+        let marker = bytes[offset + 1];
+        let length = usize::from(u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]));
+        // SOF0 through SOF15, skipping DHT (C4), JPG (C8) and DAC (CC)
+        if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+            let height = u16::from_be_bytes([bytes[offset + 5], bytes[offset + 6]]);
+            let width = u16::from_be_bytes([bytes[offset + 7], bytes[offset + 8]]);
+            return Some((u32::from(width), u32::from(height)));
+        }
+        offset += 2 + length;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    /// The type and data of every chunk, checking each CRC along the way.
+    fn chunks(png: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let (chunk_type, data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = &rest[8 + length..12 + length];
+
+            let mut expected = flate2::Crc::new();
+            expected.update(chunk_type);
+            expected.update(data);
+            assert_eq!(crc, expected.sum().to_be_bytes());
+
+            chunks.push((
+                String::from_utf8_lossy(chunk_type).into_owned(),
+                data.to_vec(),
+            ));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn encoded_pngs_decode_to_their_pixels() {
+        let pixels: Vec<u8> = (0..2 * 3 * 3).collect();
+        let png = encode_png(3, 2, &pixels).unwrap();
+
+        assert_eq!(image_size(&png), Some((3, 2)));
+        let chunks = chunks(&png);
+        let types: Vec<&str> = chunks.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(types, vec!["IHDR", "IDAT", "IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0]);
+
+        let mut decoded = Vec::new();
+        flate2::read::ZlibDecoder::new(chunks[1].1.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+        // every row starts with filter type 0
+        let mut expected = vec![0];
+        expected.extend(&pixels[..9]);
+        expected.push(0);
+        expected.extend(&pixels[9..]);
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn reads_jpeg_sizes() {
+        let jpeg = [
+            0xFF, 0xD8, // start of image
+            0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, // an APP0 segment to skip
+            0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x20, 0x00, 0x40, 0x01, 0x00, 0x00,
+        ];
+        assert_eq!(image_size(&jpeg), Some((64, 32)));
+        assert_eq!(image_size(b"GIF89a"), None);
+    }
+}
@@ -0,0 +1,227 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::*;
+
+impl ItemDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| self.fields(module, links, builder))?;
+        Ok(())
+    }
+
+    pub fn fields(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        builder: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        self.record_fields(module, links, true, builder)
+    }
+
+    fn record_fields(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        is_identified: bool,
+        builder: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        if let Some(cost) = &self.cost {
+            builder.write_string("cost", vec![XmlAttribute::string()], cost)?;
+        }
+        if let Some(description) = &self.description {
+            builder.write_raw(
+                "description",
+                vec![XmlAttribute::r#type("formattedtext")],
+                links.formatted_text(description)?,
+            )?;
+        }
+        builder.write_string(
+            "isidentified",
+            vec![XmlAttribute::number()],
+            u8::from(is_identified),
+        )?;
+        builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+        builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+        builder.write_string("source", vec![XmlAttribute::string()], &module.name)?;
+        if let Some(weight) = self.weight {
+            builder.write_string("weight", vec![XmlAttribute::number()], weight)?;
+        }
+        self.kind.process(builder)
+    }
+}
+
+impl ItemKind {
+    fn type_name(&self) -> &str {
+        match self {
+            ItemKind::Weapon { .. } => "Weapon",
+            ItemKind::Armor { .. } => "Armor",
+            ItemKind::AdventuringGear { .. } => "Adventuring Gear",
+            ItemKind::Tool { .. } => "Tools",
+            ItemKind::Other { item_type, .. } => item_type,
+        }
+    }
+
+    fn process(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        match self {
+            ItemKind::Weapon {
+                category,
+                damage,
+                damage_type,
+                properties,
+            } => {
+                let dice: Vec<String> = damage
+                    .iter()
+                    .map(|dice| format!("{}{}", dice.count, dice.dice_type))
+                    .collect();
+                w.write_string(
+                    "damage",
+                    vec![XmlAttribute::string()],
+                    format!("{} {}", dice.join("+"), damage_type),
+                )?;
+                w.write_string(
+                    "properties",
+                    vec![XmlAttribute::string()],
+                    properties.join(", "),
+                )?;
+                w.write_string("subtype", vec![XmlAttribute::string()], category)?;
+            }
+            ItemKind::Armor {
+                category,
+                armor_class,
+                dex_bonus,
+                stealth_disadvantage,
+                strength,
+            } => {
+                w.write_string("ac", vec![XmlAttribute::number()], armor_class)?;
+                let dex_bonus = match dex_bonus {
+                    ArmorDexBonus::Full => "Yes".to_owned(),
+                    ArmorDexBonus::Limited { max } => format!("Yes (max {})", max),
+                    ArmorDexBonus::None => "-".to_owned(),
+                };
+                w.write_string("dexbonus", vec![XmlAttribute::string()], dex_bonus)?;
+                if *stealth_disadvantage {
+                    w.write_string("stealth", vec![XmlAttribute::string()], "Disadvantage")?;
+                }
+                if let Some(strength) = strength {
+                    w.write_string(
+                        "strength",
+                        vec![XmlAttribute::string()],
+                        format!("Str {}", strength),
+                    )?;
+                }
+                w.write_string("subtype", vec![XmlAttribute::string()], category)?;
+            }
+            ItemKind::AdventuringGear { category } => {
+                if let Some(category) = category {
+                    w.write_string("subtype", vec![XmlAttribute::string()], category)?;
+                }
+            }
+            ItemKind::Tool { category } => {
+                if let Some(category) = category {
+                    w.write_string("subtype", vec![XmlAttribute::string()], category)?;
+                }
+            }
+            ItemKind::Other { category, .. } => {
+                if let Some(category) = category {
+                    w.write_string("subtype", vec![XmlAttribute::string()], category)?;
+                }
+            }
+        }
+        w.write_string("type", vec![XmlAttribute::string()], self.type_name())?;
+
+        Ok(())
+    }
+}
+
+impl MagicItemDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.item.id.get_id(&self.item.name));
+
+        w.child(&id, vec![], |builder| self.fields(module, links, builder))?;
+        Ok(())
+    }
+
+    pub fn fields(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        builder: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        // magic items start out unidentified, players see them by their type
+        self.item.record_fields(module, links, false, builder)?;
+        let unidentified_name = self
+            .unidentified_name
+            .as_deref()
+            .unwrap_or_else(|| self.item.kind.type_name());
+        builder.write_string(
+            "nonid_name",
+            vec![XmlAttribute::string()],
+            unidentified_name,
+        )?;
+        // the 5E ruleset keeps an item's charges in `prepared`, shown on its powers tab
+        if let Some(charges) = self.charges {
+            builder.write_string("prepared", vec![XmlAttribute::number()], charges)?;
+        }
+        self.powers(links, builder)?;
+        builder.write_string("rarity", vec![XmlAttribute::string()], self.rarity())?;
+        Ok(())
+    }
+
+    /// The 5E ruleset has no attunement field, its own items carry it in the rarity
+    /// text, as in `Rare (requires attunement)`.
+    fn rarity(&self) -> String {
+        let rarity = match self.rarity {
+            ItemRarity::Common => "Common",
+            ItemRarity::Uncommon => "Uncommon",
+            ItemRarity::Rare => "Rare",
+            ItemRarity::VeryRare => "Very Rare",
+            ItemRarity::Legendary => "Legendary",
+            ItemRarity::Artifact => "Artifact",
+        };
+
+        match &self.attunement {
+            ItemAttunement::None => rarity.to_owned(),
+            ItemAttunement::Required => format!("{} (requires attunement)", rarity),
+            ItemAttunement::RequiredBy { by } => {
+                format!("{} (requires attunement by {})", rarity, by)
+            }
+        }
+    }
+
+    fn powers(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        if self.powers.is_empty() {
+            return Ok(());
+        }
+
+        w.child("powers", vec![], |builder| {
+            let mut counter = 0;
+            for power in &self.powers {
+                counter += 1;
+                let id = format!("id-{:05}", counter);
+                builder.child(&id, vec![], |builder| {
+                    power.actions.process(builder)?;
+                    if let Some(description) = &power.description {
+                        builder.write_raw(
+                            "description",
+                            vec![XmlAttribute::r#type("formattedtext")],
+                            links.formatted_text(description)?,
+                        )?;
+                    }
+                    builder.write_string("name", vec![XmlAttribute::string()], &power.name)
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
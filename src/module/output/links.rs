@@ -0,0 +1,443 @@
+use crate::module::inputs::*;
+use super::FGUModule;
+use super::{XmlAttribute, XmlBuilder};
+use std::collections::HashMap;
+
+pub struct RecordLink {
+    pub class: String,
+    pub record_name: String,
+}
+
+/// Index of every record in the module being built, keyed by the record type
+/// and name used in `[[type:Name]]` links.
+pub struct RecordLinks {
+    links: HashMap<(String, String), RecordLink>,
+    ids: HashMap<(String, u32), String>,
+}
+
+impl RecordLinks {
+    pub fn new(module: &FGUModule) -> Result<Self, anyhow::Error> {
+        let mut links = RecordLinks {
+            links: HashMap::new(),
+            ids: HashMap::new(),
+        };
+        let module_name = &module.module.name;
+
+        for spell in &module.spells {
+            links.insert(
+                module_name,
+                "spell",
+                &spell.name,
+                "reference_spell",
+                "spell",
+                spell.id,
+            )?;
+        }
+        for table in &module.tables {
+            links.insert(
+                module_name,
+                "table",
+                &table.name,
+                "table",
+                "tables",
+                table.id,
+            )?;
+        }
+        for npc in &module.npcs {
+            links.insert(module_name, "npc", &npc.name, "npc", "npc", npc.id)?;
+        }
+        for item in &module.items {
+            links.insert(module_name, "item", &item.name, "item", "item", item.id)?;
+        }
+        for magic_item in &module.magic_items {
+            let item = &magic_item.item;
+            links.insert(module_name, "item", &item.name, "item", "item", item.id)?;
+        }
+        for feat in &module.feats {
+            links.insert(
+                module_name,
+                "feat",
+                &feat.name,
+                "reference_feat",
+                "feat",
+                feat.id,
+            )?;
+        }
+        for background in &module.backgrounds {
+            links.insert(
+                module_name,
+                "background",
+                &background.name,
+                "reference_background",
+                "background",
+                background.id,
+            )?;
+        }
+        for race in &module.races {
+            links.insert(
+                module_name,
+                "race",
+                &race.name,
+                "reference_race",
+                "race",
+                race.id,
+            )?;
+        }
+        for class in &module.classes {
+            links.insert(
+                module_name,
+                "class",
+                &class.name,
+                "reference_class",
+                "class",
+                class.id,
+            )?;
+        }
+        for subclass in &module.subclasses {
+            links.insert(
+                module_name,
+                "subclass",
+                &subclass.name,
+                "reference_class_specialization",
+                "class_specialization",
+                subclass.id,
+            )?;
+        }
+        for image in &module.module.images {
+            links.insert(
+                module_name,
+                "image",
+                &image.name,
+                "imagewindow",
+                "image",
+                image.id,
+            )?;
+        }
+        for encounter in &module.encounters {
+            links.insert(
+                module_name,
+                "encounter",
+                &encounter.name,
+                "battle",
+                "battle",
+                encounter.id,
+            )?;
+        }
+        for parcel in &module.parcels {
+            links.insert(
+                module_name,
+                "parcel",
+                &parcel.name,
+                "treasureparcel",
+                "treasureparcels",
+                parcel.id,
+            )?;
+        }
+        for quest in &module.quests {
+            links.insert(
+                module_name,
+                "quest",
+                &quest.name,
+                "quest",
+                "quest",
+                quest.id,
+            )?;
+        }
+        for story in &module.stories {
+            links.insert(
+                module_name,
+                "story",
+                &story.name,
+                "encounter",
+                "encounter",
+                story.id,
+            )?;
+        }
+
+        Ok(links)
+    }
+
+    /// Records live in one node per type, so two records of a node sharing an id would
+    /// overwrite each other in FGU. Links find records by type and name, so those have
+    /// to be unique too.
+    fn insert(
+        &mut self,
+        module_name: &str,
+        record_type: &str,
+        name: &str,
+        class: &str,
+        node: &str,
+        id: RecordId,
+    ) -> Result<(), anyhow::Error> {
+        let id = id.get_id(name);
+        if let Some(existing) = self.ids.insert((node.to_owned(), id), name.to_owned()) {
+            anyhow::bail!(
+                "{} and {} both have the {} id {}, set a different `id` on one of them",
+                existing,
+                name,
+                node,
+                id
+            );
+        }
+        let record_name = format!("{}.id-{:05}@{}", node, id, module_name);
+        let key = (record_type.to_owned(), name.to_owned());
+        if let Some(existing) = self.links.get(&key) {
+            anyhow::bail!(
+                "{} and {} are both {} records named {}, rename one so links can tell them apart",
+                existing.record_name,
+                record_name,
+                record_type,
+                name
+            );
+        }
+        self.links.insert(
+            key,
+            RecordLink {
+                class: class.to_owned(),
+                record_name,
+            },
+        );
+        Ok(())
+    }
+
+    /// Renders Markdown into FGU formatted text. Links are replaced by the record
+    /// name, and a `linklist` to the records follows the block they were in since
+    /// formatted text can't hold inline links.
+    pub fn formatted_text(&self, text: &str) -> Result<String, anyhow::Error> {
+        // the links become placeholders so the document renders in one go
+        let (marked, references) = RecordReference::replace(text, |index, _| {
+            format!("{}{}{}", LINK_START, index, LINK_END)
+        });
+        let html = markdown::to_html(&marked);
+
+        let mut formatted = String::new();
+        for block in html_blocks(&html) {
+            let mut linked = Vec::new();
+            let mut plain = String::new();
+            let mut unlinked = String::new();
+            let mut rest = block;
+            while let Some(start) = rest.find(LINK_START) {
+                let end = match rest[start..].find(LINK_END) {
+                    Some(end) => start + end,
+                    None => break,
+                };
+                plain.push_str(&rest[..start]);
+                unlinked.push_str(&rest[..start]);
+                let index = &rest[start + LINK_START.len_utf8()..end];
+                if let Some(reference) = index.parse().ok().and_then(|i: usize| references.get(i)) {
+                    plain.push_str(&escape_html(&reference.name));
+                    linked.push(reference);
+                }
+                rest = &rest[end + LINK_END.len_utf8()..];
+            }
+            plain.push_str(rest);
+            unlinked.push_str(rest);
+
+            // a block of nothing but links only becomes the link list
+            if linked.is_empty() || !strip_tags(&unlinked).trim().is_empty() {
+                formatted.push_str(&plain);
+            }
+            if !linked.is_empty() {
+                formatted.push_str(&self.link_list(&linked)?);
+            }
+        }
+        Ok(formatted)
+    }
+
+    fn link_list(&self, references: &[&RecordReference]) -> Result<String, anyhow::Error> {
+        let builder = XmlBuilder::new();
+        builder.child("linklist", vec![], |builder| {
+            for reference in references {
+                let link = self.resolve(reference)?;
+                builder.write_string(
+                    "link",
+                    vec![
+                        XmlAttribute::custom("class", link.class.as_str()),
+                        XmlAttribute::custom("recordname", link.record_name.as_str()),
+                    ],
+                    &reference.name,
+                )?;
+            }
+            Ok(())
+        })?;
+        builder.into_string()
+    }
+
+    pub fn resolve(&self, reference: &RecordReference) -> Result<&RecordLink, anyhow::Error> {
+        self.links
+            .get(&(reference.record_type.to_lowercase(), reference.name.clone()))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unable to find {} named {} in this module",
+                    reference.record_type,
+                    reference.name
+                )
+            })
+    }
+}
+
+const LINK_START: char = '\u{E000}';
+const LINK_END: char = '\u{E001}';
+
+/// Splits rendered HTML into its top level elements.
+fn html_blocks(html: &str) -> Vec<&str> {
+    let mut blocks = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    let mut index = 0;
+
+    while let Some(open) = html[index..].find('<') {
+        let open = index + open;
+        let close = match html[open..].find('>') {
+            Some(close) => open + close + 1,
+            None => break,
+        };
+        if depth == 0 {
+            start = open;
+        }
+        let tag = &html[open + 1..close - 1];
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        if tag.starts_with('/') {
+            depth = depth.saturating_sub(1);
+        } else if !tag.ends_with('/') && !matches!(name, "br" | "hr" | "img") {
+            depth += 1;
+        }
+        if depth == 0 {
+            blocks.push(&html[start..close]);
+        }
+        index = close;
+    }
+
+    blocks
+}
+
+fn strip_tags(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module() -> FGUModule {
+        let module: ModuleDefinition = serde_yaml::from_str(
+            "{name: Links, spell-files: [], table-files: [], source: Links, category: Source Book, author: Tests}",
+        )
+        .unwrap();
+        FGUModule::new(module)
+    }
+
+    fn item(yaml: &str) -> ItemDefinition {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn error(module: &FGUModule) -> String {
+        match RecordLinks::new(module) {
+            Ok(_) => panic!("expected the records to collide"),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn records_sharing_a_name_are_rejected() {
+        let mut module = module();
+        module
+            .items
+            .push(item("{name: Ring, kind: {type: adventuring-gear}}"));
+        module.magic_items.push(MagicItemDefinition {
+            item: item("{id: 12, name: Ring, kind: {type: other, item-type: Ring}}"),
+            rarity: ItemRarity::Rare,
+            attunement: ItemAttunement::None,
+            unidentified_name: None,
+            charges: None,
+            powers: Vec::new(),
+        });
+
+        assert_eq!(
+            error(&module),
+            "item.id-5354400@Links and item.id-00012@Links are both item records named Ring, rename one so links can tell them apart"
+        );
+    }
+
+    #[test]
+    fn records_sharing_an_id_are_rejected() {
+        let mut module = module();
+        module
+            .items
+            .push(item("{id: 12, name: Ring, kind: {type: adventuring-gear}}"));
+        module
+            .items
+            .push(item("{id: 12, name: Rope, kind: {type: adventuring-gear}}"));
+
+        assert_eq!(
+            error(&module),
+            "Ring and Rope both have the item id 12, set a different `id` on one of them"
+        );
+    }
+
+    fn links() -> RecordLinks {
+        let mut module = module();
+        module
+            .items
+            .push(item("{id: 12, name: Rope, kind: {type: adventuring-gear}}"));
+        RecordLinks::new(&module).unwrap()
+    }
+
+    #[test]
+    fn links_follow_the_block_they_are_in() {
+        let text = links()
+            .formatted_text("Tie the [[item:Rope]] tight.\n\nThen climb.")
+            .unwrap();
+
+        let paragraph = text.find("Tie the Rope tight.").unwrap();
+        let link_list = text.find("<linklist>").unwrap();
+        let next = text.find("Then climb.").unwrap();
+        assert!(paragraph < link_list && link_list < next, "{}", text);
+        assert!(
+            text.contains(r#"<link class="item" recordname="item.id-00012@Links">Rope</link>"#),
+            "{}",
+            text
+        );
+        assert_eq!(text.matches("<linklist>").count(), 1);
+    }
+
+    #[test]
+    fn blocks_of_only_links_become_a_link_list() {
+        let text = links().formatted_text("[[item:Rope]]").unwrap();
+
+        assert!(!text.contains("<p>"), "{}", text);
+        assert!(text.contains(r#"recordname="item.id-00012@Links">Rope</link>"#));
+    }
+
+    #[test]
+    fn unknown_links_fail_the_build() {
+        let error = links()
+            .formatted_text("Bring [[item:Ladder]] too.")
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "Unable to find item named Ladder in this module"
+        );
+    }
+}
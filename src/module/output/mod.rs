@@ -2,11 +2,26 @@ use quick_xml::events::{BytesEnd, BytesStart, BytesText, Event};
 use quick_xml::Writer;
 use std::sync::Mutex;
 
+mod background;
+mod class;
+mod encounter;
+mod feat;
+mod image;
+mod item;
+mod links;
+mod npc;
+mod parcel;
+mod quest;
+mod race;
+mod refmanual;
 mod spell;
+mod story;
 mod tables;
+mod thumbnail;
 mod fgu_module;
 
 pub use fgu_module::FGUModule;
+pub use image::image_size;
 
 struct XmlAttribute {
     prop_name: String,
@@ -0,0 +1,179 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::*;
+
+impl NpcDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            self.abilities(builder)?;
+            builder.write_string("ac", vec![XmlAttribute::number()], self.armor_class.value)?;
+            if let Some(description) = &self.armor_class.description {
+                builder.write_string(
+                    "actext",
+                    vec![XmlAttribute::string()],
+                    format!("({})", description),
+                )?;
+            }
+            NpcDefinition::powers("actions", &self.actions, builder)?;
+            builder.write_string("alignment", vec![XmlAttribute::string()], &self.alignment)?;
+            builder.write_string("cr", vec![XmlAttribute::string()], &self.challenge_rating)?;
+            if let Some(dice) = &self.hit_points.dice {
+                builder.write_string("hd", vec![XmlAttribute::string()], format!("({})", dice))?;
+            }
+            builder.write_string("hp", vec![XmlAttribute::number()], self.hit_points.average)?;
+            if let Some(languages) = &self.languages {
+                builder.write_string("languages", vec![XmlAttribute::string()], languages)?;
+            }
+            NpcDefinition::powers("lairactions", &self.lair_actions, builder)?;
+            NpcDefinition::powers("legendaryactions", &self.legendary_actions, builder)?;
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            NpcDefinition::powers("reactions", &self.reactions, builder)?;
+            if !self.saving_throws.is_empty() {
+                builder.write_string(
+                    "savingthrows",
+                    vec![XmlAttribute::string()],
+                    self.saving_throws(),
+                )?;
+            }
+            if let Some(senses) = &self.senses {
+                builder.write_string("senses", vec![XmlAttribute::string()], senses)?;
+            }
+            builder.write_string("size", vec![XmlAttribute::string()], &self.size)?;
+            if !self.skills.is_empty() {
+                builder.write_string("skills", vec![XmlAttribute::string()], self.skills())?;
+            }
+            builder.write_string("source", vec![XmlAttribute::string()], &module.name)?;
+            builder.write_string("speed", vec![XmlAttribute::string()], &self.speed)?;
+            if let Some(description) = &self.description {
+                builder.write_raw(
+                    "text",
+                    vec![XmlAttribute::r#type("formattedtext")],
+                    links.formatted_text(description)?,
+                )?;
+            }
+            NpcDefinition::powers("traits", &self.traits, builder)?;
+            builder.write_string("type", vec![XmlAttribute::string()], &self.creature_type)?;
+            if let Some(xp) = challenge_xp(&self.challenge_rating) {
+                builder.write_string("xp", vec![XmlAttribute::number()], xp)?;
+            }
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn abilities(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let scores = &self.abilities;
+        w.child("abilities", vec![], |builder| {
+            for (ability, score) in &[
+                (AbilityScore::Charisma, scores.charisma),
+                (AbilityScore::Constitution, scores.constitution),
+                (AbilityScore::Dexterity, scores.dexterity),
+                (AbilityScore::Intelligence, scores.intelligence),
+                (AbilityScore::Strength, scores.strength),
+                (AbilityScore::Wisdom, scores.wisdom),
+            ] {
+                builder.child(&ability.to_long_name(), vec![], |builder| {
+                    builder.write_string(
+                        "bonus",
+                        vec![XmlAttribute::number()],
+                        ability_modifier(*score),
+                    )?;
+                    builder.write_string("score", vec![XmlAttribute::number()], score)
+                })?;
+            }
+            Ok(())
+        })
+    }
+
+    fn saving_throws(&self) -> String {
+        self.saving_throws
+            .iter()
+            .map(|save| format!("{} {:+}", save.ability.to_short_name(), save.bonus))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn skills(&self) -> String {
+        self.skills
+            .iter()
+            .map(|skill| format!("{} {:+}", skill.skill, skill.bonus))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn powers(name: &str, powers: &[NpcPower], w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        if powers.is_empty() {
+            return Ok(());
+        }
+
+        w.child(name, vec![], |builder| {
+            let mut counter = 0;
+            for power in powers {
+                counter += 1;
+                let id = format!("id-{:05}", counter);
+                builder.child(&id, vec![], |builder| {
+                    builder.write_string(
+                        "desc",
+                        vec![XmlAttribute::string()],
+                        &power.description,
+                    )?;
+                    builder.write_string("name", vec![XmlAttribute::string()], &power.name)
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn ability_modifier(score: u8) -> i16 {
+    (i16::from(score) - 10).div_euclid(2)
+}
+
+pub fn challenge_xp(challenge_rating: &str) -> Option<u32> {
+    let xp = match challenge_rating.trim() {
+        "0" => 10,
+        "1/8" => 25,
+        "1/4" => 50,
+        "1/2" => 100,
+        "1" => 200,
+        "2" => 450,
+        "3" => 700,
+        "4" => 1_100,
+        "5" => 1_800,
+        "6" => 2_300,
+        "7" => 2_900,
+        "8" => 3_900,
+        "9" => 5_000,
+        "10" => 5_900,
+        "11" => 7_200,
+        "12" => 8_400,
+        "13" => 10_000,
+        "14" => 11_500,
+        "15" => 13_000,
+        "16" => 15_000,
+        "17" => 18_000,
+        "18" => 20_000,
+        "19" => 22_000,
+        "20" => 25_000,
+        "21" => 33_000,
+        "22" => 41_000,
+        "23" => 50_000,
+        "24" => 62_000,
+        "25" => 75_000,
+        "26" => 90_000,
+        "27" => 105_000,
+        "28" => 120_000,
+        "29" => 135_000,
+        "30" => 155_000,
+        _ => return None,
+    };
+    Some(xp)
+}
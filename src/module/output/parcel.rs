@@ -0,0 +1,105 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::FGUModule;
+use super::*;
+
+impl ParcelDefinition {
+    pub fn process(
+        &self,
+        module: &FGUModule,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            self.coins.process(builder)?;
+            self.items(module, links, builder)?;
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)
+        })?;
+        Ok(())
+    }
+
+    fn items(
+        &self,
+        module: &FGUModule,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        if self.items.is_empty() {
+            return Ok(());
+        }
+
+        w.child("itemlist", vec![], |builder| {
+            let mut counter = 0;
+            for parcel_item in &self.items {
+                counter += 1;
+                let id = format!("id-{:05}", counter);
+                match parcel_item {
+                    ParcelItem::Reference { item, count } => {
+                        builder.child(&id, vec![], |builder| {
+                            builder.write_string("count", vec![XmlAttribute::number()], count)?;
+                            if let Some(found) = module.items.iter().find(|i| &i.name == item) {
+                                found.fields(&module.module, links, builder)
+                            } else if let Some(found) =
+                                module.magic_items.iter().find(|i| &i.item.name == item)
+                            {
+                                found.fields(&module.module, links, builder)
+                            } else {
+                                Err(anyhow::anyhow!(
+                                    "Parcel {} references item {}, which is not in this module",
+                                    self.name,
+                                    item
+                                ))
+                            }
+                        })?;
+                    }
+                    ParcelItem::Named { name, count } => {
+                        builder.child(&id, vec![], |builder| {
+                            builder.write_string("count", vec![XmlAttribute::number()], count)?;
+                            builder.write_string(
+                                "isidentified",
+                                vec![XmlAttribute::number()],
+                                1,
+                            )?;
+                            builder.write_string("name", vec![XmlAttribute::string()], name)
+                        })?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ParcelCoins {
+    fn process(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let coins: Vec<(&str, u32)> = vec![
+            ("PP", self.pp),
+            ("GP", self.gp),
+            ("EP", self.ep),
+            ("SP", self.sp),
+            ("CP", self.cp),
+        ]
+        .into_iter()
+        .filter(|(_, amount)| *amount > 0)
+        .collect();
+        if coins.is_empty() {
+            return Ok(());
+        }
+
+        w.child("coinlist", vec![], |builder| {
+            let mut counter = 0;
+            for (currency, amount) in &coins {
+                counter += 1;
+                let id = format!("id-{:05}", counter);
+                builder.child(&id, vec![], |builder| {
+                    builder.write_string("amount", vec![XmlAttribute::number()], amount)?;
+                    builder.write_string("description", vec![XmlAttribute::string()], currency)
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,27 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::*;
+
+impl QuestDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            builder.write_raw(
+                "description",
+                vec![XmlAttribute::r#type("formattedtext")],
+                links.formatted_text(&self.description)?,
+            )?;
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            builder.write_string("source", vec![XmlAttribute::string()], &module.name)?;
+            builder.write_string("xp", vec![XmlAttribute::number()], self.xp)
+        })?;
+        Ok(())
+    }
+}
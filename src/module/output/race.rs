@@ -0,0 +1,159 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::*;
+
+impl RaceDefinition {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            builder.write_string("source", vec![XmlAttribute::string()], &module.name)?;
+            self.subraces(links, builder)?;
+            if let Some(description) = &self.description {
+                builder.write_raw(
+                    "text",
+                    vec![XmlAttribute::r#type("formattedtext")],
+                    links.formatted_text(description)?,
+                )?;
+            }
+            builder.child("traits", vec![], |builder| {
+                let mut counter = 0;
+                if !self.ability_score_increases.is_empty() {
+                    counter += 1;
+                    process_trait(
+                        counter,
+                        "Ability Score Increase",
+                        &ability_score_increase_text(&self.ability_score_increases),
+                        None,
+                        builder,
+                    )?;
+                }
+                counter += 1;
+                process_trait(
+                    counter,
+                    "Size",
+                    &format!("<p>Your size is {}.</p>", self.size),
+                    None,
+                    builder,
+                )?;
+                counter += 1;
+                process_trait(
+                    counter,
+                    "Speed",
+                    &format!("<p>Your base walking speed is {} feet.</p>", self.speed),
+                    None,
+                    builder,
+                )?;
+                for race_trait in &self.traits {
+                    counter += 1;
+                    process_trait(
+                        counter,
+                        &race_trait.name,
+                        &links.formatted_text(&race_trait.description)?,
+                        Some(&race_trait.actions),
+                        builder,
+                    )?;
+                }
+                Ok(())
+            })?;
+            Ok(())
+        })?;
+        Ok(())
+    }
+
+    fn subraces(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        if self.subraces.is_empty() {
+            return Ok(());
+        }
+
+        w.child("subraces", vec![], |builder| {
+            let mut counter = 0;
+            for subrace in &self.subraces {
+                counter += 1;
+                let id = format!("id-{:05}", counter);
+                builder.child(&id, vec![], |builder| subrace.process(links, builder))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl SubraceDefinition {
+    fn process(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        w.write_string("locked", vec![XmlAttribute::number()], 1)?;
+        w.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+        if let Some(description) = &self.description {
+            w.write_raw(
+                "text",
+                vec![XmlAttribute::r#type("formattedtext")],
+                links.formatted_text(description)?,
+            )?;
+        }
+        w.child("traits", vec![], |builder| {
+            let mut counter = 0;
+            if !self.ability_score_increases.is_empty() {
+                counter += 1;
+                process_trait(
+                    counter,
+                    "Ability Score Increase",
+                    &ability_score_increase_text(&self.ability_score_increases),
+                    None,
+                    builder,
+                )?;
+            }
+            for race_trait in &self.traits {
+                counter += 1;
+                process_trait(
+                    counter,
+                    &race_trait.name,
+                    &links.formatted_text(&race_trait.description)?,
+                    Some(&race_trait.actions),
+                    builder,
+                )?;
+            }
+            Ok(())
+        })
+    }
+}
+
+fn process_trait(
+    index: usize,
+    name: &str,
+    text: &str,
+    actions: Option<&SpellActions>,
+    w: &XmlBuilder,
+) -> Result<(), anyhow::Error> {
+    let id = format!("id-{:05}", index);
+    w.child(&id, vec![], |builder| {
+        if let Some(actions) = actions {
+            if !actions.is_empty() {
+                actions.process(builder)?;
+            }
+        }
+        builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+        builder.write_string("name", vec![XmlAttribute::string()], name)?;
+        builder.write_raw("text", vec![XmlAttribute::r#type("formattedtext")], text)
+    })
+}
+
+fn ability_score_increase_text(increases: &[AbilityScoreIncrease]) -> String {
+    increases
+        .iter()
+        .map(|increase| {
+            let mut name = increase.ability.to_long_name();
+            name[..1].make_ascii_uppercase();
+            format!(
+                "<p>Your {} score increases by {}.</p>",
+                name, increase.increase
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("")
+}
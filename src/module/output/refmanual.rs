@@ -0,0 +1,202 @@
+use crate::module::inputs::*;
+use super::image::{archive_path, image_size};
+use super::links::RecordLinks;
+use super::*;
+use anyhow::Context;
+use std::cell::Cell;
+use std::path::Path;
+
+impl ReferenceManual {
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        w.child(
+            "reference",
+            vec![XmlAttribute::custom("static", "true")],
+            |builder| {
+                self.index(module, builder)?;
+                self.data(links, builder)
+            },
+        )
+    }
+
+    pub fn images(&self) -> Vec<&Path> {
+        let mut images = Vec::new();
+        for page in self.pages() {
+            for block in &page.blocks {
+                if let ReferenceBlock::Image { path, .. } = block {
+                    images.push(path.as_path());
+                }
+            }
+        }
+        images
+    }
+
+    fn pages(&self) -> Vec<&ReferencePage> {
+        self.chapters
+            .iter()
+            .flat_map(|chapter| chapter.subchapters.iter())
+            .flat_map(|subchapter| subchapter.pages.iter())
+            .collect()
+    }
+
+    fn index(&self, module: &ModuleDefinition, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let page_counter = Cell::new(0);
+
+        w.child("refmanualindex", vec![], |builder| {
+            builder.child("chapters", vec![], |builder| {
+                let mut chapter_counter = 0;
+                for chapter in &self.chapters {
+                    chapter_counter += 1;
+                    let id = format!("chapter_{:05}", chapter_counter);
+                    builder.child(&id, vec![], |builder| {
+                        builder.write_string(
+                            "name",
+                            vec![XmlAttribute::string()],
+                            &chapter.name,
+                        )?;
+                        builder.child("subchapters", vec![], |builder| {
+                            let mut subchapter_counter = 0;
+                            for subchapter in &chapter.subchapters {
+                                subchapter_counter += 1;
+                                let id = format!("subchapter_{:05}", subchapter_counter);
+                                builder.child(&id, vec![], |builder| {
+                                    builder.write_string(
+                                        "name",
+                                        vec![XmlAttribute::string()],
+                                        &subchapter.name,
+                                    )?;
+                                    builder.child("refpages", vec![], |builder| {
+                                        for page in &subchapter.pages {
+                                            page_counter.set(page_counter.get() + 1);
+                                            process_page_link(
+                                                page_counter.get(),
+                                                page,
+                                                module,
+                                                builder,
+                                            )?;
+                                        }
+                                        Ok(())
+                                    })
+                                })?;
+                            }
+                            Ok(())
+                        })
+                    })?;
+                }
+                Ok(())
+            })
+        })
+    }
+
+    fn data(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        w.child("refmanualdata", vec![], |builder| {
+            let mut page_counter = 0;
+            for page in self.pages() {
+                page_counter += 1;
+                let id = format!("refpage_{:05}", page_counter);
+                builder.child(&id, vec![], |builder| {
+                    builder.child("blocks", vec![], |builder| {
+                        let mut block_counter = 0;
+                        for block in &page.blocks {
+                            block_counter += 1;
+                            let id = format!("block_{:05}", block_counter);
+                            builder.child(&id, vec![], |builder| block.process(links, builder))?;
+                        }
+                        Ok(())
+                    })?;
+                    builder.write_string("name", vec![XmlAttribute::string()], &page.name)
+                })?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ReferenceBlock {
+    fn process(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        match self {
+            ReferenceBlock::Text(text) => {
+                w.write_string("blocktype", vec![XmlAttribute::string()], "singletext")?;
+                w.write_raw(
+                    "text",
+                    vec![XmlAttribute::r#type("formattedtext")],
+                    links.formatted_text(text)?,
+                )?;
+            }
+            ReferenceBlock::Image { path, caption } => {
+                w.write_string("blocktype", vec![XmlAttribute::string()], "image")?;
+                w.write_string("caption", vec![XmlAttribute::string()], caption)?;
+                w.child("image", vec![XmlAttribute::r#type("image")], |builder| {
+                    builder.write_string("bitmap", vec![], archive_path(path))
+                })?;
+                let bytes = std::fs::read(path).with_context(|| {
+                    format!("Unable to read reference manual image {}", path.display())
+                })?;
+                if let Some((width, height)) = image_size(&bytes) {
+                    w.write_string(
+                        "size",
+                        vec![XmlAttribute::string()],
+                        format!("{},{}", width, height),
+                    )?;
+                }
+            }
+            ReferenceBlock::Links(references) => {
+                w.write_string("blocktype", vec![XmlAttribute::string()], "singletext")?;
+                w.child(
+                    "text",
+                    vec![XmlAttribute::r#type("formattedtext")],
+                    |builder| {
+                        builder.child("linklist", vec![], |builder| {
+                            for reference in references {
+                                let link = links.resolve(reference)?;
+                                builder.write_string(
+                                    "link",
+                                    vec![
+                                        XmlAttribute::custom("class", link.class.as_str()),
+                                        XmlAttribute::custom(
+                                            "recordname",
+                                            link.record_name.as_str(),
+                                        ),
+                                    ],
+                                    &reference.name,
+                                )?;
+                            }
+                            Ok(())
+                        })
+                    },
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn process_page_link(
+    index: usize,
+    page: &ReferencePage,
+    module: &ModuleDefinition,
+    w: &XmlBuilder,
+) -> Result<(), anyhow::Error> {
+    let id = format!("refpage_{:05}", index);
+    w.child(&id, vec![], |builder| {
+        builder.write_string("keywords", vec![XmlAttribute::string()], "")?;
+        builder.child(
+            "listlink",
+            vec![XmlAttribute::custom("type", "windowreference")],
+            |builder| {
+                builder.write_string("class", vec![], "reference_manualtextwide")?;
+                builder.write_string(
+                    "recordname",
+                    vec![],
+                    format!("reference.refmanualdata.{}@{}", id, module.name),
+                )
+            },
+        )?;
+        builder.write_string("name", vec![XmlAttribute::string()], &page.name)
+    })
+}
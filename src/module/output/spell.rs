@@ -1,14 +1,21 @@
 use crate::module::inputs::*;
+use super::links::RecordLinks;
 use super::*;
 use tracing::trace;
 
 impl SpellDefinition {
-    pub fn process(&self, module: &ModuleDefinition, w: &XmlBuilder) -> Result<(), anyhow::Error> {
-        let id = format!("id-{:05}", self.id.get_id());
+    pub fn process(
+        &self,
+        module: &ModuleDefinition,
+        links: &RecordLinks,
+        w: &XmlBuilder,
+    ) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
 
         w.child(&id, vec![], |builder| {
             self.casting_time(builder)?;
-            self.description(builder)?;
+            self.components(builder)?;
+            self.description(links, builder)?;
             self.duration(builder)?;
             self.spell_level(builder)?;
             builder.write_string("locked", vec![XmlAttribute::number()], "1")?;
@@ -58,11 +65,23 @@ impl SpellDefinition {
         Ok(())
     }
 
+    fn components(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        if let Some(components) = &self.components {
+            w.write_string("components", vec![XmlAttribute::string()], components)?;
+        }
+
+        Ok(())
+    }
+
     fn casting_time(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
         let casting_time = match self.casting_time {
             SpellCastDuration::Reaction => "1 reaction".to_owned(),
             SpellCastDuration::BonusAction { count } => format!("{} bonus action", count),
             SpellCastDuration::Action { count } => format!("{} action", count),
+            SpellCastDuration::Minutes { count: 1 } => "1 minute".to_owned(),
+            SpellCastDuration::Minutes { count } => format!("{} minutes", count),
+            SpellCastDuration::Hours { count: 1 } => "1 hour".to_owned(),
+            SpellCastDuration::Hours { count } => format!("{} hours", count),
             SpellCastDuration::Instant => "instant".to_owned(),
             SpellCastDuration::Forever => "forever".to_owned(),
         };
@@ -70,8 +89,8 @@ impl SpellDefinition {
         w.write_string("castingtime", vec![XmlAttribute::string()], casting_time)
     }
 
-    fn description(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
-        let description = markdown::to_html(&self.description);
+    fn description(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let description = links.formatted_text(&self.description)?;
         w.write_raw(
             "description",
             vec![XmlAttribute::r#type("formattedtext")],
@@ -81,23 +100,36 @@ impl SpellDefinition {
 
     fn actions(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
         trace!("Actions: {:?}", self.actions);
+        self.actions.process(w)
+    }
+}
+
+impl SpellActions {
+    pub fn is_empty(&self) -> bool {
+        self.attacks.is_empty()
+            && self.saves.is_empty()
+            && self.damages.is_empty()
+            && self.effects.is_empty()
+    }
+
+    pub fn process(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
         w.child("actions", vec![], |builder| {
             let mut count = 1;
-            for action in &self.actions.attacks {
+            for action in &self.attacks {
                 count += 1;
-                SpellDefinition::process_action(count, || process_attack(action, w), builder)?;
+                SpellActions::process_action(count, || process_attack(action, w), builder)?;
             }
-            for action in &self.actions.saves {
+            for action in &self.saves {
                 count += 1;
-                SpellDefinition::process_action(count, || process_save(action, w), builder)?;
+                SpellActions::process_action(count, || process_save(action, w), builder)?;
             }
-            for action in &self.actions.damages {
+            for action in &self.damages {
                 count += 1;
-                SpellDefinition::process_action(count, || process_damage(action, w), builder)?;
+                SpellActions::process_action(count, || process_damage(action, w), builder)?;
             }
-            for action in &self.actions.effects {
+            for action in &self.effects {
                 count += 1;
-                SpellDefinition::process_action(count, || process_effect(action, w), builder)?;
+                SpellActions::process_action(count, || process_effect(action, w), builder)?;
             }
             Ok(())
         })?;
@@ -106,7 +138,7 @@ impl SpellDefinition {
     }
 
     fn process_action<F>(
-        index: u8,
+        index: usize,
         action: F,
         w: &XmlBuilder,
     ) -> Result<(), anyhow::Error> where F: Fn() -> Result<(), anyhow::Error> {
@@ -126,7 +158,10 @@ fn process_save(cast: &SpellSave, w: &XmlBuilder) -> Result<(), anyhow::Error> {
             w.write_string("savedcmod", vec![XmlAttribute::number()], 0)?;
             w.write_string("savedcprof", vec![XmlAttribute::number()], 1)?;
         }
-        ToSave::Fixed{ value: _ } => {}
+        ToSave::Fixed { value } => {
+            w.write_string("savedcbase", vec![XmlAttribute::string()], "fixed")?;
+            w.write_string("savedcmod", vec![XmlAttribute::number()], value)?;
+        }
         ToSave::Ability(custom) => {
             w.write_string("savedcbase", vec![XmlAttribute::string()], "ability")?;
             w.write_string("savedcmod", vec![XmlAttribute::number()], custom.bonus)?;
@@ -236,6 +271,9 @@ fn process_damage(action_damage: &ActionDamage, w: &XmlBuilder) -> Result<(), an
                     vec![XmlAttribute::r#type("dice")],
                     dice_list,
                 )?;
+                if let Some(bonus) = dmg_element.bonus {
+                    builder.write_string("bonus", vec![XmlAttribute::number()], bonus)?;
+                }
 
                 let spell_stat = match &dmg_element.modifier {
                     DamageModifier::AbilityScore { ability } => Some(ability.to_long_name()),
@@ -0,0 +1,20 @@
+use crate::module::inputs::*;
+use super::links::RecordLinks;
+use super::*;
+
+impl StoryDefinition {
+    pub fn process(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+
+        w.child(&id, vec![], |builder| {
+            builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
+            builder.write_raw(
+                "text",
+                vec![XmlAttribute::r#type("formattedtext")],
+                links.formatted_text(&self.text)?,
+            )
+        })?;
+        Ok(())
+    }
+}
@@ -1,15 +1,38 @@
 use crate::module::inputs::*;
+use super::links::RecordLinks;
 use super::*;
 
 impl TableDefinition {
-    pub fn process(&self, w: &XmlBuilder) -> Result<(), anyhow::Error> {
+    pub fn process(&self, links: &RecordLinks, w: &XmlBuilder) -> Result<(), anyhow::Error> {
         let note_text = self
             .formatted_text
             .as_ref()
             .cloned()
             .unwrap_or_default();
-        let note_text = markdown::to_html(&note_text);
-        let id = format!("id-{:05}", self.id);
+        let note_text = links.formatted_text(&note_text)?;
+        let id = format!("id-{:05}", self.id.get_id(&self.name));
+        let dice = self.dice()?;
+        let columns = self.column_labels();
+        for range in &self.ranges {
+            let results = range.results();
+            if results.len() != columns.len() {
+                anyhow::bail!(
+                    "Table {} range {}-{} has {} results, but the table has {} columns",
+                    self.name,
+                    range.from,
+                    range.until,
+                    results.len(),
+                    columns.len()
+                );
+            }
+        }
+        // FGU lists every die on its own, so 2d6 becomes d6,d6
+        let dice = dice
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|dice| vec![dice.dice_type.as_str(); usize::from(dice.count)])
+            .collect::<Vec<_>>()
+            .join(",");
 
         w.child(&id, vec![], |builder| {
             builder.write_string(
@@ -17,12 +40,19 @@ impl TableDefinition {
                 vec![XmlAttribute::string()],
                 &self.description,
             )?;
-            builder.write_string("dice", vec![XmlAttribute::custom("type", "dice")], "")?;
+            builder.write_string("dice", vec![XmlAttribute::custom("type", "dice")], &dice)?;
             builder.write_string("enabled", vec![XmlAttribute::number()], 0)?;
             builder.write_string("hiddenenabled", vec![XmlAttribute::string()], "Disabled")?;
             builder.write_string("hiderollresults", vec![XmlAttribute::number()], 0)?;
-            builder.write_string("labelcol1", vec![XmlAttribute::string()], "Effect")?;
+            for (index, label) in columns.iter().enumerate() {
+                builder.write_string(
+                    &format!("labelcol{}", index + 1),
+                    vec![XmlAttribute::string()],
+                    label,
+                )?;
+            }
             builder.write_string("locked", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("mod", vec![XmlAttribute::number()], self.modifier)?;
             builder.write_string("mode", vec![XmlAttribute::number()], 0)?;
             builder.write_string("name", vec![XmlAttribute::string()], &self.name)?;
             w.write_raw(
@@ -30,13 +60,13 @@ impl TableDefinition {
                 vec![XmlAttribute::custom("type", "formattedtext")],
                 &note_text,
             )?;
-            builder.write_string("resultscols", vec![XmlAttribute::number()], 1)?;
+            builder.write_string("resultscols", vec![XmlAttribute::number()], columns.len())?;
             builder.write_string("table_positionoffset", vec![XmlAttribute::number()], 0)?;
             builder.child("tablerows", vec![], |builder| {
                 let mut counter = 0;
                 for range in &self.ranges {
                     counter += 1;
-                    TableDefinition::process_table_row(range, counter, builder)?;
+                    TableDefinition::process_table_row(range, counter, links, builder)?;
                 }
 
                 Ok(())
@@ -48,7 +78,8 @@ impl TableDefinition {
 
     fn process_table_row(
         range: &TableRange,
-        index: u8,
+        index: usize,
+        links: &RecordLinks,
         w: &XmlBuilder,
     ) -> Result<(), anyhow::Error> {
         let id = format!("id-{:05}", index);
@@ -56,23 +87,100 @@ impl TableDefinition {
             builder.write_string("fromrange", vec![XmlAttribute::number()], range.from)?;
             builder.write_string("torange", vec![XmlAttribute::number()], range.until)?;
             builder.child("results", vec![], |builder| {
-                builder.child("id-00001", vec![], |builder| {
-                    //yolo, idk when you would need more than 1
-                    builder.write_string(
-                        "result",
-                        vec![XmlAttribute::string()],
-                        &range.description,
-                    )?;
-                    builder.child(
-                        "resultlink",
-                        vec![XmlAttribute::custom("type", "windowreference")],
-                        |builder| {
-                            builder.empty_node("class", vec![])?;
-                            builder.empty_node("recordname", vec![])
-                        },
-                    )
-                })
+                let mut counter = 0;
+                for result in range.results() {
+                    counter += 1;
+                    let id = format!("id-{:05}", counter);
+                    // only the first column opens the linked record
+                    let link = match &range.link {
+                        Some(reference) if counter == 1 => Some(links.resolve(reference)?),
+                        _ => None,
+                    };
+                    builder.child(&id, vec![], |builder| {
+                        builder.write_string("result", vec![XmlAttribute::string()], result)?;
+                        builder.child(
+                            "resultlink",
+                            vec![XmlAttribute::custom("type", "windowreference")],
+                            |builder| match link {
+                                Some(link) => {
+                                    builder.write_string("class", vec![], &link.class)?;
+                                    builder.write_string("recordname", vec![], &link.record_name)
+                                }
+                                None => {
+                                    builder.empty_node("class", vec![])?;
+                                    builder.empty_node("recordname", vec![])
+                                }
+                            },
+                        )
+                    })?;
+                }
+                Ok(())
             })
         })
     }
+
+    fn dice(&self) -> Result<Option<Vec<Dice>>, anyhow::Error> {
+        match &self.dice {
+            Some(text) => match Dice::parse_expression(text) {
+                Some(dice) => Ok(Some(dice)),
+                None => Err(anyhow::anyhow!(
+                    "Table {} has an invalid dice expression '{}'",
+                    self.name,
+                    text
+                )),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn column_labels(&self) -> Vec<&str> {
+        if self.columns.is_empty() {
+            vec!["Effect"]
+        } else {
+            self.columns.iter().map(String::as_str).collect()
+        }
+    }
+}
+
+impl TableRange {
+    fn results(&self) -> Vec<&str> {
+        if self.results.is_empty() {
+            vec![&self.description]
+        } else {
+            self.results.iter().map(String::as_str).collect()
+        }
+    }
+}
+
+/// Results linking to another table get rolled by FGU as well, so make sure no chain of
+/// nested rolls leads back to a table already being rolled.
+pub fn check_nested_tables(tables: &[TableDefinition]) -> Result<(), anyhow::Error> {
+    for table in tables {
+        let mut path = vec![table.name.as_str()];
+        check_nested_table(table, tables, &mut path)?;
+    }
+    Ok(())
+}
+
+fn check_nested_table<'a>(
+    table: &'a TableDefinition,
+    tables: &'a [TableDefinition],
+    path: &mut Vec<&'a str>,
+) -> Result<(), anyhow::Error> {
+    for range in &table.ranges {
+        let reference = match &range.link {
+            Some(reference) if reference.record_type.eq_ignore_ascii_case("table") => reference,
+            _ => continue,
+        };
+        if path.contains(&reference.name.as_str()) {
+            path.push(&reference.name);
+            anyhow::bail!("Nested table rolls never finish: {}", path.join(" -> "));
+        }
+        if let Some(nested) = tables.iter().find(|nested| nested.name == reference.name) {
+            path.push(&nested.name);
+            check_nested_table(nested, tables, path)?;
+            path.pop();
+        }
+    }
+    Ok(())
 }
@@ -0,0 +1,233 @@
+use super::image::{encode_png, image_size};
+
+const THUMBNAIL_SIZE: usize = 100;
+const MIN_THUMBNAIL_SIZE: u32 = 32;
+const MAX_THUMBNAIL_SIZE: u32 = 512;
+
+const BACKGROUND: [u8; 3] = [40, 40, 48];
+const BORDER: [u8; 3] = [200, 170, 90];
+const TEXT: [u8; 3] = [230, 230, 230];
+
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+
+pub fn validate_thumbnail(bytes: &[u8]) -> Result<(), anyhow::Error> {
+    if !bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        anyhow::bail!("Module thumbnail must be a PNG file");
+    }
+
+    let (width, height) =
+        image_size(bytes).ok_or_else(|| anyhow::anyhow!("Unable to read thumbnail size"))?;
+    let allowed = MIN_THUMBNAIL_SIZE..=MAX_THUMBNAIL_SIZE;
+    if !allowed.contains(&width) || !allowed.contains(&height) {
+        anyhow::bail!(
+            "Module thumbnail is {}x{}, it must be between {} and {} pixels on each side",
+            width,
+            height,
+            MIN_THUMBNAIL_SIZE,
+            MAX_THUMBNAIL_SIZE
+        );
+    }
+
+    Ok(())
+}
+
+/// Draws a plain thumbnail with the module name on it, used when the module
+/// definition does not provide one.
+pub fn placeholder_thumbnail(module_name: &str) -> Result<Vec<u8>, anyhow::Error> {
+    let mut pixels = vec![0u8; THUMBNAIL_SIZE * THUMBNAIL_SIZE * 3];
+    for y in 0..THUMBNAIL_SIZE {
+        for x in 0..THUMBNAIL_SIZE {
+            let is_border = x < 3 || y < 3 || x >= THUMBNAIL_SIZE - 3 || y >= THUMBNAIL_SIZE - 3;
+            let color = if is_border { BORDER } else { BACKGROUND };
+            set_pixel(&mut pixels, x, y, color);
+        }
+    }
+
+    let mut scale = 2;
+    let mut lines = wrap_text(module_name, max_line_length(scale));
+    if lines.len() * line_height(scale) > THUMBNAIL_SIZE - 12 {
+        scale = 1;
+        lines = wrap_text(module_name, max_line_length(scale));
+    }
+    lines.truncate((THUMBNAIL_SIZE - 12) / line_height(scale));
+
+    let text_height = lines.len() * line_height(scale);
+    let mut top = (THUMBNAIL_SIZE - text_height) / 2;
+    for line in lines {
+        let line_width = line.chars().count() * (GLYPH_WIDTH + 1) * scale;
+        let mut left = (THUMBNAIL_SIZE - line_width) / 2;
+        for c in line.chars() {
+            draw_glyph(&mut pixels, glyph(c), left, top, scale);
+            left += (GLYPH_WIDTH + 1) * scale;
+        }
+        top += line_height(scale);
+    }
+
+    encode_png(THUMBNAIL_SIZE as u32, THUMBNAIL_SIZE as u32, &pixels)
+}
+
+fn max_line_length(scale: usize) -> usize {
+    (THUMBNAIL_SIZE - 12) / ((GLYPH_WIDTH + 1) * scale)
+}
+
+fn line_height(scale: usize) -> usize {
+    (GLYPH_HEIGHT + 3) * scale
+}
+
+fn wrap_text(text: &str, max_length: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = String::new();
+    for word in text.to_uppercase().split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        while word.len() > max_length {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            lines.push(word.drain(..max_length).collect());
+        }
+        let word: String = word.into_iter().collect();
+
+        if current.is_empty() {
+            current = word;
+        } else if current.chars().count() + 1 + word.chars().count() <= max_length {
+            current.push(' ');
+            current.push_str(&word);
+        } else {
+            lines.push(std::mem::replace(&mut current, word));
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn set_pixel(pixels: &mut [u8], x: usize, y: usize, color: [u8; 3]) {
+    let offset = (y * THUMBNAIL_SIZE + x) * 3;
+    pixels[offset..offset + 3].copy_from_slice(&color);
+}
+
+fn draw_glyph(pixels: &mut [u8], rows: [u8; GLYPH_HEIGHT], left: usize, top: usize, scale: usize) {
+    for (row_index, row) in rows.iter().enumerate() {
+        for column in 0..GLYPH_WIDTH {
+            if row & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                continue;
+            }
+            for dy in 0..scale {
+                for dx in 0..scale {
+                    let x = left + column * scale + dx;
+                    let y = top + row_index * scale + dy;
+                    if x < THUMBNAIL_SIZE && y < THUMBNAIL_SIZE {
+                        set_pixel(pixels, x, y, TEXT);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn glyph(c: char) -> [u8; GLYPH_HEIGHT] {
+    match c {
+        'A' => [0b01110, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'B' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10001, 0b10001, 0b11110],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b11110],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'F' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b10000],
+        'G' => [0b01110, 0b10001, 0b10000, 0b10111, 0b10001, 0b10001, 0b01111],
+        'H' => [0b10001, 0b10001, 0b10001, 0b11111, 0b10001, 0b10001, 0b10001],
+        'I' => [0b01110, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        'J' => [0b00111, 0b00010, 0b00010, 0b00010, 0b00010, 0b10010, 0b01100],
+        'K' => [0b10001, 0b10010, 0b10100, 0b11000, 0b10100, 0b10010, 0b10001],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'M' => [0b10001, 0b11011, 0b10101, 0b10101, 0b10001, 0b10001, 0b10001],
+        'N' => [0b10001, 0b10001, 0b11001, 0b10101, 0b10011, 0b10001, 0b10001],
+        'O' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'Q' => [0b01110, 0b10001, 0b10001, 0b10001, 0b10101, 0b10010, 0b01101],
+        'R' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10100, 0b10010, 0b10001],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        'T' => [0b11111, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00100],
+        'U' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01110],
+        'V' => [0b10001, 0b10001, 0b10001, 0b10001, 0b10001, 0b01010, 0b00100],
+        'W' => [0b10001, 0b10001, 0b10001, 0b10101, 0b10101, 0b10101, 0b01010],
+        'X' => [0b10001, 0b10001, 0b01010, 0b00100, 0b01010, 0b10001, 0b10001],
+        'Y' => [0b10001, 0b10001, 0b10001, 0b01010, 0b00100, 0b00100, 0b00100],
+        'Z' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b10000, 0b11111],
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        '-' => [0b00000, 0b00000, 0b00000, 0b11111, 0b00000, 0b00000, 0b00000],
+        '.' => [0b00000, 0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b01100],
+        ',' => [0b00000, 0b00000, 0b00000, 0b00000, 0b01100, 0b00100, 0b01000],
+        '\'' => [0b00100, 0b00100, 0b01000, 0b00000, 0b00000, 0b00000, 0b00000],
+        ':' => [0b00000, 0b01100, 0b01100, 0b00000, 0b01100, 0b01100, 0b00000],
+        '!' => [0b00100, 0b00100, 0b00100, 0b00100, 0b00100, 0b00000, 0b00100],
+        '&' => [0b01100, 0b10010, 0b10100, 0b01000, 0b10101, 0b10010, 0b01101],
+        ' ' => [0; GLYPH_HEIGHT],
+        _ => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b00000, 0b00100],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let pixels = vec![0u8; width as usize * height as usize * 3];
+        encode_png(width, height, &pixels).unwrap()
+    }
+
+    fn error(bytes: &[u8]) -> String {
+        validate_thumbnail(bytes).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn thumbnails_must_fit_the_size_limits() {
+        assert!(validate_thumbnail(&png(32, 512)).is_ok());
+        assert_eq!(
+            error(&png(16, 64)),
+            "Module thumbnail is 16x64, it must be between 32 and 512 pixels on each side"
+        );
+        assert_eq!(
+            error(&png(100, 600)),
+            "Module thumbnail is 100x600, it must be between 32 and 512 pixels on each side"
+        );
+    }
+
+    #[test]
+    fn thumbnails_must_be_png_files() {
+        assert_eq!(
+            error(&[0xFF, 0xD8, 0xFF, 0xE0]),
+            "Module thumbnail must be a PNG file"
+        );
+        assert_eq!(error(b"\x89PNG\r\n\x1a\n"), "Unable to read thumbnail size");
+    }
+
+    #[test]
+    fn placeholders_are_valid_thumbnails() {
+        let thumbnail = placeholder_thumbnail("The Grim Hollow Campaign Guide").unwrap();
+
+        assert!(thumbnail.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(image_size(&thumbnail), Some((100, 100)));
+        assert!(validate_thumbnail(&thumbnail).is_ok());
+    }
+
+    #[test]
+    fn long_names_are_wrapped() {
+        assert_eq!(wrap_text("Grim Hollow", 14), vec!["GRIM HOLLOW"]);
+        assert_eq!(
+            wrap_text("The Grim Hollow Campaign", 8),
+            vec!["THE GRIM", "HOLLOW", "CAMPAIGN"]
+        );
+        assert_eq!(wrap_text("Abcdefghij", 4), vec!["ABCD", "EFGH", "IJ"]);
+    }
+}
@@ -0,0 +1,629 @@
+use super::inputs::*;
+use serde_yaml::{Mapping, Value};
+use std::fmt;
+
+/// A field that came back different after building a module and extracting it again.
+pub struct RoundtripProblem {
+    pub record: String,
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for RoundtripProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.field.is_empty() {
+            write!(f, "{}: {}", self.record, self.message)
+        } else {
+            write!(f, "{}: `{}` {}", self.record, self.field, self.message)
+        }
+    }
+}
+
+/// Fields of a spell that a module has no place for, so they are lost when building.
+pub fn lossy_spell_fields(spell: &SpellDefinition) -> Vec<String> {
+    let mut fields = Vec::new();
+    for (index, attack) in spell.actions.attacks.iter().enumerate() {
+        // FGU rolls attacks with the spell group's bonus
+        if !matches!(attack.save, ToSave::DC) {
+            fields.push(format!("actions.attacks[{}].save", index));
+        }
+    }
+    for (index, effect) in spell.actions.effects.iter().enumerate() {
+        if let SpellEffectDuration::Finite(duration) = &effect.duration {
+            if duration.count == 0 {
+                fields.push(format!("actions.effects[{}].duration", index));
+            }
+        }
+    }
+    fields
+}
+
+/// Compares spells as they were loaded with the ones extracted from the built module.
+pub fn compare_spells(
+    original: &[SpellDefinition],
+    extracted: &[SpellDefinition],
+) -> Result<Vec<RoundtripProblem>, anyhow::Error> {
+    let mut problems = Vec::new();
+    for spell in original {
+        let record = format!("Spell {}", spell.name);
+        match extracted.iter().find(|other| other.name == spell.name) {
+            Some(other) => compare(
+                &record,
+                &normalize_spell(spell)?,
+                &normalize_spell(other)?,
+                &mut problems,
+            ),
+            None => problems.push(missing(record)),
+        }
+    }
+    Ok(problems)
+}
+
+/// Compares tables as they were loaded with the ones extracted from the built module.
+pub fn compare_tables(
+    original: &[TableDefinition],
+    extracted: &[TableDefinition],
+) -> Result<Vec<RoundtripProblem>, anyhow::Error> {
+    let mut problems = Vec::new();
+    for table in original {
+        let record = format!("Table {}", table.name);
+        match extracted.iter().find(|other| other.name == table.name) {
+            Some(other) => compare(
+                &record,
+                &normalize_table(table)?,
+                &normalize_table(other)?,
+                &mut problems,
+            ),
+            None => problems.push(missing(record)),
+        }
+    }
+    Ok(problems)
+}
+
+fn missing(record: String) -> RoundtripProblem {
+    RoundtripProblem {
+        record,
+        field: String::new(),
+        message: "is missing from the built module".to_owned(),
+    }
+}
+
+/// Writes the spell out as YAML values, with the things that are allowed to change
+/// between the YAML and the module written the same way on both sides.
+fn normalize_spell(spell: &SpellDefinition) -> Result<Value, anyhow::Error> {
+    let mut value = serde_yaml::to_value(spell)?;
+    if let Value::Mapping(fields) = &mut value {
+        set(fields, "id", Value::from(spell.id.get_id(&spell.name)));
+        set(
+            fields,
+            "description",
+            Value::from(normalize_markdown(&spell.description)),
+        );
+        for name in &["short-description", "duration", "components"] {
+            let text = fields.get(&Value::from(*name)).and_then(Value::as_str);
+            if text.map(str::trim) == Some("") {
+                set(fields, name, Value::Null);
+            }
+        }
+    }
+    Ok(value)
+}
+
+fn normalize_table(table: &TableDefinition) -> Result<Value, anyhow::Error> {
+    let mut value = serde_yaml::to_value(table)?;
+    if let Value::Mapping(fields) = &mut value {
+        set(fields, "id", Value::from(table.id.get_id(&table.name)));
+        let notes = table.formatted_text.as_deref().unwrap_or_default();
+        set(
+            fields,
+            "formatted-text",
+            Value::from(normalize_markdown(notes)),
+        );
+        // 2d6 and d6+d6 roll the same
+        let dice = table
+            .dice
+            .as_deref()
+            .map(|text| match Dice::parse_expression(text) {
+                Some(dice) => dice
+                    .iter()
+                    .flat_map(|dice| vec![dice.dice_type.as_str(); usize::from(dice.count)])
+                    .collect::<Vec<_>>()
+                    .join("+"),
+                None => text.to_owned(),
+            });
+        set(fields, "dice", serde_yaml::to_value(dice)?);
+        // no columns is a single "Effect" column, whose results sit in the description
+        if table.columns.is_empty() {
+            set(fields, "columns", serde_yaml::to_value(vec!["Effect"])?);
+        }
+        let ranges = table
+            .ranges
+            .iter()
+            .map(|range| {
+                let mut range_value = serde_yaml::to_value(range)?;
+                if let Value::Mapping(range_fields) = &mut range_value {
+                    let results = if range.results.is_empty() {
+                        vec![range.description.as_str()]
+                    } else {
+                        range.results.iter().map(String::as_str).collect()
+                    };
+                    range_fields.remove(&Value::from("description"));
+                    set(range_fields, "results", serde_yaml::to_value(results)?);
+                }
+                Ok(range_value)
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
+        set(fields, "ranges", Value::Sequence(ranges));
+    }
+    Ok(value)
+}
+
+fn set(fields: &mut Mapping, name: &str, value: Value) {
+    fields.insert(Value::from(name), value);
+}
+
+/// Markdown as it comes back out of a module: links moved into their own block after
+/// the one they were in, list markers as `-`, and the lines of a paragraph joined the
+/// way Markdown renders them. Blank lines still separate blocks and list items keep
+/// their own lines, so losing either shows up as a change.
+fn normalize_markdown(text: &str) -> String {
+    let mut blocks = Vec::new();
+    for block in markdown_blocks(text) {
+        let (plain, references) = RecordReference::extract(&block);
+        let links_only = !references.is_empty()
+            && block
+                .lines()
+                .all(|line| line.trim().is_empty() || RecordReference::parse(line).is_some());
+        if !links_only {
+            blocks.push(normalize_block(&plain));
+        }
+        if !references.is_empty() {
+            let links: Vec<String> = references
+                .iter()
+                .map(|reference| {
+                    format!(
+                        "[[{}:{}]]",
+                        reference.record_type.to_lowercase(),
+                        reference.name
+                    )
+                })
+                .collect();
+            blocks.push(links.join("\n"));
+        }
+    }
+    blocks.join("\n\n")
+}
+
+/// Splits Markdown on blank lines, however many there are.
+fn markdown_blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !lines.is_empty() {
+                blocks.push(lines.join("\n"));
+                lines.clear();
+            }
+        } else {
+            lines.push(line);
+        }
+    }
+    if !lines.is_empty() {
+        blocks.push(lines.join("\n"));
+    }
+    blocks
+}
+
+fn normalize_block(block: &str) -> String {
+    // each line as how deep it's quoted and what it says
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    let mut in_code = false;
+    let mut joins_next = false;
+
+    for line in block.lines() {
+        if in_code || line.trim_start().starts_with("```") {
+            in_code ^= line.trim_start().starts_with("```");
+            lines.push((0, line.trim_end().to_owned()));
+            joins_next = false;
+            continue;
+        }
+
+        let mut content = line.trim();
+        let mut depth = 0;
+        while let Some(quoted) = content.strip_prefix('>') {
+            content = quoted.trim_start();
+            depth += 1;
+        }
+        let mut content = content.split_whitespace().collect::<Vec<_>>().join(" ");
+        if content.starts_with("* ") || content.starts_with("+ ") {
+            content.replace_range(..1, "-");
+        }
+
+        let continues = joins_next
+            && !starts_block(&content)
+            && matches!(lines.last(), Some((last_depth, _)) if *last_depth == depth);
+        // two trailing spaces break the line, and headings and table rows never continue
+        joins_next =
+            !line.ends_with("  ") && !content.starts_with('#') && !content.starts_with('|');
+        match lines.last_mut() {
+            Some((_, last)) if continues => {
+                last.push(' ');
+                last.push_str(&content);
+            }
+            _ => lines.push((depth, content)),
+        }
+    }
+
+    lines
+        .iter()
+        .map(|(depth, line)| format!("{}{}", "> ".repeat(*depth), line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether a line starts a new block instead of continuing a paragraph.
+fn starts_block(line: &str) -> bool {
+    let ordered = line
+        .find(". ")
+        .map(|dot| dot > 0 && line[..dot].chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or_default();
+    ordered
+        || line.starts_with("- ")
+        || line.starts_with('#')
+        || line.starts_with('|')
+        || line.starts_with("```")
+        || line == "---"
+}
+
+fn compare(
+    record: &str,
+    original: &Value,
+    extracted: &Value,
+    problems: &mut Vec<RoundtripProblem>,
+) {
+    compare_at(record, "", original, extracted, problems)
+}
+
+fn compare_at(
+    record: &str,
+    field: &str,
+    original: &Value,
+    extracted: &Value,
+    problems: &mut Vec<RoundtripProblem>,
+) {
+    match (original, extracted) {
+        (Value::Mapping(original), Value::Mapping(extracted)) => {
+            let mut keys: Vec<&Value> = original.iter().map(|(key, _)| key).collect();
+            keys.extend(
+                extracted
+                    .iter()
+                    .map(|(key, _)| key)
+                    .filter(|key| !original.contains_key(key)),
+            );
+            for key in keys {
+                let name = key.as_str().unwrap_or_default();
+                let path = if field.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{}.{}", field, name)
+                };
+                compare_at(
+                    record,
+                    &path,
+                    original.get(key).unwrap_or(&Value::Null),
+                    extracted.get(key).unwrap_or(&Value::Null),
+                    problems,
+                );
+            }
+        }
+        (Value::Sequence(original), Value::Sequence(extracted))
+            if original.len() == extracted.len() =>
+        {
+            for (index, (original, extracted)) in original.iter().zip(extracted).enumerate() {
+                let path = format!("{}[{}]", field, index);
+                compare_at(record, &path, original, extracted, problems);
+            }
+        }
+        (original, extracted) if original != extracted => problems.push(RoundtripProblem {
+            record: record.to_owned(),
+            field: field.to_owned(),
+            message: format!(
+                "was {} but came back as {}",
+                describe(original),
+                describe(extracted)
+            ),
+        }),
+        _ => {}
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => "missing".to_owned(),
+        Value::Sequence(items) => format!("a list of {}", items.len()),
+        value => serde_yaml::to_string(value)
+            .map(|text| text.trim_start_matches("---").trim().to_owned())
+            .unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::module::extract::{extract_module, ExtractedModule};
+    use crate::module::output::FGUModule;
+
+    const SPELL: &str = r#"
+id: 42
+name: Searing Frost
+short-description: Cold that burns
+description: |
+  # Searing Frost
+
+  A **freezing** blast, roll on [[table:Frost Effects]] when it hits.
+
+  - Lasts while you concentrate
+  - Ends early in fire
+duration: Concentration, up to 1 minute
+components: V, S, M (a shard of ice)
+casting-time:
+  type: bonus-action
+  count: 2
+school: Evocation
+spell-level:
+  type: Level
+  number: 3
+needs-preperation: true
+is-ritual: true
+group: Spells (Level 3)
+actions:
+  attacks:
+    - range: ranged
+      save: { type: dc }
+    - range: melee
+      save: { type: dc }
+  saves:
+    - is-magic: true
+      stat: { type: ability-score, ability: con }
+      save: { type: dc }
+    - is-magic: false
+      stat: { type: ability-score, ability: dex }
+      save: { type: fixed, value: 15 }
+    - is-magic: false
+      stat: { type: ability-score, ability: wis }
+      save:
+        type: ability
+        stat: { type: ability-score, ability: int }
+        is-proficient: true
+        bonus: 2
+  damages:
+    - damage:
+        - modifier: { damage-mod: ability-score, ability: int }
+          damage-type: cold
+          dice: [{ dice-type: d8, count: 3 }, { dice-type: d6, count: 1 }]
+        - modifier: { damage-mod: none }
+          damage-type: fire
+          dice: [{ dice-type: d4, count: 2 }]
+          bonus: 3
+  effects:
+    - effect: "Frozen; SPEED: -10"
+      duration: { time-scale: finite, count: 10, unit: minute }
+      targets-self: false
+    - effect: Warded
+      duration: { time-scale: finite, count: 1, unit: hour }
+      targets-self: true
+    - effect: Chilled
+      duration: { time-scale: finite, count: 3, unit: round }
+      targets-self: false
+    - effect: Numb
+      duration: { time-scale: indefinite }
+      targets-self: true
+"#;
+
+    const TABLES: &[&str] = &[
+        r#"
+name: Frost Effects
+description: What the frost does
+formatted-text: Roll when **Searing Frost** hits.
+columns: [Effect, Duration]
+dice: 1d4+d4
+modifier: -1
+ranges:
+  - from: 1
+    until: 3
+    results: [Slowed, 1 round]
+    link: { type: spell, name: Searing Frost }
+  - from: 4
+    until: 7
+    results: [Frozen solid, 1 minute]
+"#,
+        r#"
+id: 7
+name: Weather
+description: Today's weather
+dice: d6
+ranges:
+  - from: 1
+    until: 3
+    description: Snow
+  - from: 4
+    until: 6
+    description: Sleet
+    link: { type: table, name: Frost Effects }
+"#,
+    ];
+
+    fn spell(yaml: &str) -> SpellDefinition {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn simple_spell(name: &str, casting_time: &str, level: &str) -> SpellDefinition {
+        spell(&format!(
+            r#"
+name: {}
+description: Plain text
+casting-time: {}
+school: Abjuration
+spell-level: {}
+needs-preperation: false
+is-ritual: false
+group: Spells
+actions: {{}}
+"#,
+            name, casting_time, level
+        ))
+    }
+
+    fn roundtrip(
+        name: &str,
+        spells: Vec<SpellDefinition>,
+        tables: Vec<TableDefinition>,
+    ) -> (FGUModule, ExtractedModule) {
+        let module: ModuleDefinition = serde_yaml::from_str(
+            "{name: Roundtrip, spell-files: [], table-files: [], source: Roundtrip, category: Source Book, author: Tests}",
+        )
+        .unwrap();
+        let mut fgu_module = FGUModule::new(module);
+        fgu_module.spells = spells;
+        fgu_module.tables = tables;
+
+        let path = std::env::temp_dir().join(format!("fguctl-{}-{}.mod", name, std::process::id()));
+        fgu_module.process(path.to_str().unwrap()).unwrap();
+        let extracted = extract_module(&path);
+        std::fs::remove_file(&path).unwrap();
+        (fgu_module, extracted.unwrap())
+    }
+
+    fn assert_lossless(problems: Vec<RoundtripProblem>) {
+        let problems: Vec<String> = problems.iter().map(ToString::to_string).collect();
+        assert!(problems.is_empty(), "{}", problems.join("\n"));
+    }
+
+    fn all_tables() -> Vec<TableDefinition> {
+        TABLES
+            .iter()
+            .map(|yaml| serde_yaml::from_str(yaml).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn every_spell_field_survives_a_roundtrip() {
+        let (built, extracted) = roundtrip("spell", vec![spell(SPELL)], all_tables());
+        assert_lossless(compare_spells(&built.spells, &extracted.spells).unwrap());
+    }
+
+    #[test]
+    fn every_casting_time_and_level_survives_a_roundtrip() {
+        let spells = vec![
+            simple_spell("Instant", "{type: instant}", "{type: Cantrip}"),
+            simple_spell("Reaction", "{type: reaction}", "{type: Level, number: 1}"),
+            simple_spell(
+                "Bonus",
+                "{type: bonus-action, count: 1}",
+                "{type: Level, number: 9}",
+            ),
+            simple_spell(
+                "Action",
+                "{type: action, count: 3}",
+                "{type: Level, number: 2}",
+            ),
+            simple_spell("Forever", "{type: forever}", "{type: Cantrip}"),
+            simple_spell(
+                "Minutes",
+                "{type: minutes, count: 10}",
+                "{type: Level, number: 4}",
+            ),
+            simple_spell("Hours", "{type: hours, count: 1}", "{type: Level, number: 5}"),
+        ];
+        assert!(spells
+            .iter()
+            .all(|spell| lossy_spell_fields(spell).is_empty()));
+
+        let (built, extracted) = roundtrip("casting", spells, Vec::new());
+        assert_lossless(compare_spells(&built.spells, &extracted.spells).unwrap());
+    }
+
+    #[test]
+    fn every_table_field_survives_a_roundtrip() {
+        let (built, extracted) = roundtrip("table", vec![spell(SPELL)], all_tables());
+        assert_eq!(extracted.tables.len(), TABLES.len());
+        assert_lossless(compare_tables(&built.tables, &extracted.tables).unwrap());
+    }
+
+    #[test]
+    fn lossy_fields_are_flagged() {
+        let mut spell = simple_spell("Lossy", "{type: instant}", "{type: Cantrip}");
+        spell.actions.attacks.push(SpellRange {
+            range: AttackRange::Melee,
+            save: ToSave::Fixed { value: 12 },
+        });
+        spell.actions.effects.push(SpellEffect {
+            effect: "Blink".to_owned(),
+            duration: SpellEffectDuration::Finite(SpellEffectDurationFinite {
+                count: 0,
+                unit: TimeUnit::Round,
+            }),
+            targets_self: true,
+        });
+        let lossy = lossy_spell_fields(&spell);
+        assert_eq!(
+            lossy,
+            vec!["actions.attacks[0].save", "actions.effects[0].duration"]
+        );
+
+        let (built, extracted) = roundtrip("lossy", vec![spell], Vec::new());
+        let problems = compare_spells(&built.spells, &extracted.spells).unwrap();
+        assert!(!problems.is_empty());
+        assert!(problems
+            .iter()
+            .all(|problem| lossy.iter().any(|field| problem.field.starts_with(field))));
+    }
+
+    #[test]
+    fn markdown_is_compared_the_way_modules_store_it() {
+        assert_eq!(
+            normalize_markdown("See [[Spell:Fire Bolt]]\nfor more.\n\n* one\n* two"),
+            normalize_markdown("See Fire Bolt for more.\n\n[[spell:Fire Bolt]]\n\n- one\n- two")
+        );
+        assert_ne!(normalize_markdown("one"), normalize_markdown("two"));
+    }
+
+    #[test]
+    fn markdown_keeps_its_blocks_apart() {
+        assert_eq!(
+            normalize_markdown("A long\nparagraph.  \n\n\n\n> quoted\n> text"),
+            normalize_markdown("A long paragraph.\n\n> quoted text")
+        );
+        assert_ne!(
+            normalize_markdown("One.\n\nTwo."),
+            normalize_markdown("One. Two.")
+        );
+        assert_ne!(
+            normalize_markdown("# Title\n\nText"),
+            normalize_markdown("Title Text")
+        );
+        assert_ne!(
+            normalize_markdown("- one\n- two"),
+            normalize_markdown("- one - two")
+        );
+        assert_ne!(
+            normalize_markdown("```\nfn a() {}\n\nfn b() {}\n```"),
+            normalize_markdown("```\nfn a() {} fn b() {}\n```")
+        );
+    }
+
+    #[test]
+    fn lost_paragraph_breaks_are_reported() {
+        let mut original = simple_spell("Breaks", "{type: instant}", "{type: Cantrip}");
+        original.description = "First paragraph.\n\nSecond paragraph.".to_owned();
+        let mut extracted = simple_spell("Breaks", "{type: instant}", "{type: Cantrip}");
+        extracted.description = "First paragraph. Second paragraph.".to_owned();
+
+        let problems = compare_spells(&[original], &[extracted]).unwrap();
+        let fields: Vec<&str> = problems
+            .iter()
+            .map(|problem| problem.field.as_str())
+            .collect();
+        assert_eq!(fields, vec!["description"]);
+    }
+}
@@ -0,0 +1,290 @@
+use super::inputs::*;
+use std::fmt;
+
+/// A problem with the ranges of a table, pointing back at the YAML it came from.
+pub struct TableProblem {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TableProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.file, self.line, self.message)
+        }
+    }
+}
+
+/// Checks that every possible roll of the table lands in exactly one range, reporting
+/// inverted ranges, ranges beyond the dice, overlaps and gaps.
+pub fn validate_table(table: &TableDefinition) -> Vec<TableProblem> {
+    let mut problems = Vec::new();
+    let problem = |line: usize, message: String| TableProblem {
+        file: table.path.display().to_string(),
+        line,
+        message: format!("{}: {}", table.name, message),
+    };
+
+    let bounds = match &table.dice {
+        Some(text) => match Dice::parse_expression(text) {
+            Some(dice) => Some(roll_bounds(&dice, table.modifier)),
+            None => {
+                problems.push(problem(0, format!("invalid dice expression '{}'", text)));
+                None
+            }
+        },
+        None => None,
+    };
+
+    let mut ranges: Vec<&TableRange> = Vec::new();
+    for range in &table.ranges {
+        if range.from > range.until {
+            problems.push(problem(
+                range.line,
+                format!("range {}-{} is inverted", range.from, range.until),
+            ));
+            continue;
+        }
+        if let Some((lowest, highest)) = bounds {
+            if range.from < lowest || range.until > highest {
+                problems.push(problem(
+                    range.line,
+                    format!(
+                        "range {}-{} is outside the possible rolls {}-{}",
+                        range.from, range.until, lowest, highest
+                    ),
+                ));
+            }
+        }
+        for other in &ranges {
+            if range.from <= other.until && other.from <= range.until {
+                problems.push(problem(
+                    range.line,
+                    format!(
+                        "range {}-{} overlaps range {}-{} on line {}",
+                        range.from, range.until, other.from, other.until, other.line
+                    ),
+                ));
+            }
+        }
+        ranges.push(range);
+    }
+
+    if ranges.is_empty() {
+        return problems;
+    }
+    ranges.sort_by_key(|range| (range.from, range.until));
+    let (lowest, highest) = bounds.unwrap_or_else(|| {
+        let first = ranges.first().map(|range| range.from).unwrap_or_default();
+        let last = ranges
+            .iter()
+            .map(|range| range.until)
+            .max()
+            .unwrap_or_default();
+        (first, last)
+    });
+
+    let mut next = lowest;
+    for range in &ranges {
+        if range.from > next {
+            problems.push(problem(range.line, gap_message(next, range.from - 1)));
+        }
+        next = next.max(range.until + 1);
+    }
+    if next <= highest {
+        let line = ranges.last().map(|range| range.line).unwrap_or_default();
+        problems.push(problem(line, gap_message(next, highest)));
+    }
+
+    problems
+}
+
+/// Finds the line each entry of the top level `ranges` list starts on.
+pub fn range_lines(contents: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut in_ranges = false;
+    let mut item_indent = None;
+
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - line.trim_start().len();
+        if indent == 0 && !trimmed.starts_with('-') {
+            in_ranges = trimmed.starts_with("ranges:");
+            continue;
+        }
+        let is_item = trimmed == "-" || trimmed.starts_with("- ");
+        if in_ranges && is_item && indent == *item_indent.get_or_insert(indent) {
+            lines.push(index + 1);
+        }
+    }
+
+    lines
+}
+
+fn roll_bounds(dice: &[Dice], modifier: i16) -> (i32, i32) {
+    let lowest: i32 = dice.iter().map(|dice| i32::from(dice.count)).sum();
+    let highest: i32 = dice
+        .iter()
+        .map(|dice| i32::from(dice.count) * i32::from(dice.sides()))
+        .sum();
+    (lowest + i32::from(modifier), highest + i32::from(modifier))
+}
+
+fn gap_message(from: i32, until: i32) -> String {
+    if from == until {
+        format!("no result for a roll of {}", from)
+    } else {
+        format!("no result for rolls {}-{}", from, until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(contents: &str) -> TableDefinition {
+        let mut table: TableDefinition = serde_yaml::from_str(contents).unwrap();
+        table.path = "table.yaml".into();
+        for (range, line) in table.ranges.iter_mut().zip(range_lines(contents)) {
+            range.line = line;
+        }
+        table
+    }
+
+    fn messages(contents: &str) -> Vec<String> {
+        validate_table(&table(contents))
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn accepts_ranges_covering_every_roll() {
+        let problems = messages(
+            r#"
+name: Weather
+description: ""
+dice: 2d6
+ranges:
+  - from: 2
+    until: 6
+    description: Rain
+  - from: 7
+    until: 12
+    description: Sun
+"#,
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+
+    #[test]
+    fn accepts_ranges_below_zero_from_a_modifier() {
+        let problems = messages(
+            r#"
+name: Fumbles
+description: ""
+dice: d6
+modifier: -3
+ranges:
+  - from: -2
+    until: 0
+    description: Drop your weapon
+  - from: 1
+    until: 3
+    description: Stumble
+"#,
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+    }
+
+    #[test]
+    fn reports_overlapping_ranges() {
+        let problems = messages(
+            r#"
+name: Loot
+description: ""
+dice: d6
+ranges:
+  - from: 1
+    until: 4
+    description: Copper
+  - from: 4
+    until: 6
+    description: Silver
+"#,
+        );
+        assert_eq!(
+            problems,
+            vec!["table.yaml:9: Loot: range 4-6 overlaps range 1-4 on line 6"]
+        );
+    }
+
+    #[test]
+    fn reports_gaps() {
+        let problems = messages(
+            r#"
+name: Loot
+description: ""
+dice: d10
+ranges:
+  - from: 1
+    until: 4
+    description: Copper
+  - from: 7
+    until: 8
+    description: Silver
+"#,
+        );
+        assert_eq!(
+            problems,
+            vec![
+                "table.yaml:9: Loot: no result for rolls 5-6",
+                "table.yaml:9: Loot: no result for rolls 9-10",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_inverted_ranges() {
+        let problems = messages(
+            r#"
+name: Loot
+description: ""
+ranges:
+  - from: 1
+    until: 2
+    description: Copper
+  - from: 4
+    until: 3
+    description: Silver
+"#,
+        );
+        assert_eq!(problems, vec!["table.yaml:8: Loot: range 4-3 is inverted"]);
+    }
+
+    #[test]
+    fn reports_ranges_beyond_the_dice() {
+        let problems = messages(
+            r#"
+name: Loot
+description: ""
+dice: d4
+modifier: 1
+ranges:
+  - from: 1
+    until: 5
+    description: Copper
+"#,
+        );
+        assert_eq!(
+            problems,
+            vec!["table.yaml:7: Loot: range 1-5 is outside the possible rolls 2-5"]
+        );
+    }
+}